@@ -0,0 +1,63 @@
+//! Encoder setup and `testsrc` content shared by the benchmarks. The content is
+//! deterministic, so numbers stay comparable between runs and between the openh264
+//! versions `build.rs` can select.
+
+// Each benchmark uses a different subset.
+#![allow(dead_code)]
+
+use openh264_sys::testsrc::{Pattern, TestSource};
+use openh264_sys::*;
+
+/// Frames of one size from `testsrc`.
+pub struct Sequence {
+    pub width: usize,
+    pub height: usize,
+    pub frames: Vec<Frame>,
+}
+
+pub fn sequence(pattern: Pattern, width: usize, height: usize, frames: usize) -> Sequence {
+    Sequence {
+        width,
+        height,
+        frames: TestSource::new(pattern, width, height)
+            .take(frames)
+            .collect(),
+    }
+}
+
+impl Sequence {
+    /// Encodes every frame, starting with an IDR, and returns the access units.
+    pub fn encode(&self, encoder: &mut Encoder) -> Vec<Vec<u8>> {
+        encoder.force_intra_frame(true).unwrap();
+        let mut access_units = Vec::with_capacity(self.frames.len());
+        for frame in &self.frames {
+            let encoded = encoder.encode(&frame.as_yuv().unwrap()).unwrap();
+            access_units.push(encoded.to_bytes());
+        }
+        access_units
+    }
+}
+
+/// Single-layer parameters at 30 fps; callers adjust them before `initialize`.
+pub fn params(encoder: &Encoder, width: usize, height: usize, bitrate: i32) -> SEncParamExt {
+    let mut param = encoder.default_params().unwrap();
+    param.iUsageType = CAMERA_VIDEO_REAL_TIME;
+    param.iPicWidth = width as i32;
+    param.iPicHeight = height as i32;
+    param.fMaxFrameRate = 30.0;
+    param.iTargetBitrate = bitrate;
+    param.iSpatialLayerNum = 1;
+    param.sSpatialLayers[0].iVideoWidth = width as i32;
+    param.sSpatialLayers[0].iVideoHeight = height as i32;
+    param.sSpatialLayers[0].fFrameRate = 30.0;
+    param.sSpatialLayers[0].iSpatialBitrate = bitrate;
+    param
+}
+
+pub fn codec_version() -> String {
+    let version = unsafe { WelsGetCodecVersion() };
+    format!(
+        "{}.{}.{}",
+        version.uMajor, version.uMinor, version.uRevision
+    )
+}
@@ -0,0 +1,36 @@
+//! Encoding throughput of a synthetic 1080p sequence for every encoder thread count.
+
+#[macro_use]
+extern crate criterion;
+extern crate openh264_sys;
+
+mod common;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use openh264_sys::testsrc::Pattern;
+use openh264_sys::threading::EncoderThreads;
+use openh264_sys::*;
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+const FRAMES: usize = 8;
+
+fn encode_threads(c: &mut Criterion) {
+    let sequence = common::sequence(Pattern::Gradient, WIDTH, HEIGHT, FRAMES);
+    let mut group = c.benchmark_group("encode_1080p_threads");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(FRAMES as u64));
+    for threads in 1..=MAX_THREADS_NUM as u16 {
+        let mut encoder = Encoder::new().unwrap();
+        let mut param = common::params(&encoder, WIDTH, HEIGHT, 5_000_000);
+        EncoderThreads::new(threads).apply(&mut param).unwrap();
+        encoder.initialize(&param).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, _| {
+            b.iter(|| sequence.encode(&mut encoder))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode_threads);
+criterion_main!(benches);
@@ -0,0 +1,158 @@
+//! Encode and decode throughput across resolutions, usage types, rate control modes and
+//! complexity modes. Criterion reports frames per second; the average encoded size per
+//! frame of each configuration is printed before it is measured.
+
+#[macro_use]
+extern crate criterion;
+extern crate openh264_sys;
+
+mod common;
+
+use criterion::{BenchmarkId, Criterion, Throughput};
+use openh264_sys::rate_control::{FrameSkip, RateControl, MAX_QP};
+use openh264_sys::testsrc::Pattern;
+use openh264_sys::*;
+
+const FRAMES: usize = 16;
+const BITRATE: i32 = 1_000_000;
+
+/// Encodes `sequence` with `param`, once to report the encoded size and then under
+/// criterion.
+fn bench_encode(
+    c: &mut Criterion,
+    group: &str,
+    id: &str,
+    sequence: &common::Sequence,
+    param: &SEncParamExt,
+) {
+    let mut encoder = Encoder::new().unwrap();
+    encoder.initialize(param).unwrap();
+    let bytes: usize = sequence.encode(&mut encoder).iter().map(Vec::len).sum();
+    println!(
+        "{}/{}: {} bytes/frame (openh264 {})",
+        group,
+        id,
+        bytes / sequence.frames.len(),
+        common::codec_version()
+    );
+
+    let mut group = c.benchmark_group(group);
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(sequence.frames.len() as u64));
+    group.bench_function(BenchmarkId::from_parameter(id), |b| {
+        b.iter(|| sequence.encode(&mut encoder))
+    });
+    group.finish();
+}
+
+fn resolutions(c: &mut Criterion) {
+    for &(width, height) in &[(320, 180), (640, 360), (1280, 720), (1920, 1080)] {
+        let sequence = common::sequence(Pattern::Gradient, width, height, FRAMES);
+        let param = common::params(&Encoder::new().unwrap(), width, height, BITRATE);
+        let id = format!("{}x{}", width, height);
+        bench_encode(c, "encode_resolution", &id, &sequence, &param);
+    }
+}
+
+fn usage_types(c: &mut Criterion) {
+    let usages = [
+        ("camera_real_time", CAMERA_VIDEO_REAL_TIME),
+        ("screen_real_time", SCREEN_CONTENT_REAL_TIME),
+        ("camera_non_real_time", CAMERA_VIDEO_NON_REAL_TIME),
+    ];
+    for &(id, usage) in &usages {
+        let sequence = if usage == SCREEN_CONTENT_REAL_TIME {
+            common::sequence(Pattern::ScrollingText, 640, 360, FRAMES)
+        } else {
+            common::sequence(Pattern::Gradient, 640, 360, FRAMES)
+        };
+        let mut param = common::params(&Encoder::new().unwrap(), 640, 360, BITRATE);
+        param.iUsageType = usage;
+        bench_encode(c, "encode_usage", id, &sequence, &param);
+    }
+}
+
+fn rc_modes(c: &mut Criterion) {
+    let bitrate = BITRATE as u32;
+    let modes = [
+        (
+            "quality",
+            RateControl::Quality {
+                bitrate,
+                min_qp: 0,
+                max_qp: MAX_QP,
+            },
+        ),
+        (
+            "bitrate",
+            RateControl::Bitrate {
+                bitrate,
+                max_bitrate: None,
+                frame_skip: FrameSkip::BeforeEncoding,
+            },
+        ),
+        (
+            "buffer_based",
+            RateControl::Buffer {
+                min_qp: 0,
+                max_qp: MAX_QP,
+            },
+        ),
+        ("off", RateControl::ConstantQp { qp: 26 }),
+    ];
+    let sequence = common::sequence(Pattern::Gradient, 640, 360, FRAMES);
+    for &(id, rate_control) in &modes {
+        let mut param = common::params(&Encoder::new().unwrap(), 640, 360, BITRATE);
+        rate_control.apply(&mut param).unwrap();
+        bench_encode(c, "encode_rc_mode", id, &sequence, &param);
+    }
+}
+
+fn complexity_modes(c: &mut Criterion) {
+    let modes = [
+        ("low", LOW_COMPLEXITY),
+        ("medium", MEDIUM_COMPLEXITY),
+        ("high", HIGH_COMPLEXITY),
+    ];
+    let sequence = common::sequence(Pattern::Gradient, 640, 360, FRAMES);
+    for &(id, mode) in &modes {
+        let mut param = common::params(&Encoder::new().unwrap(), 640, 360, BITRATE);
+        param.iComplexityMode = mode;
+        bench_encode(c, "encode_complexity", id, &sequence, &param);
+    }
+}
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode_resolution");
+    group.sample_size(10);
+    for &(width, height) in &[(320, 180), (640, 360), (1280, 720), (1920, 1080)] {
+        let mut encoder = Encoder::new().unwrap();
+        let param = common::params(&encoder, width, height, BITRATE);
+        encoder.initialize(&param).unwrap();
+        let access_units =
+            common::sequence(Pattern::Gradient, width, height, FRAMES).encode(&mut encoder);
+        let mut decoder = Decoder::new().unwrap();
+        decoder.initialize(&SDecodingParam::default()).unwrap();
+
+        group.throughput(Throughput::Elements(access_units.len() as u64));
+        let id = format!("{}x{}", width, height);
+        group.bench_function(BenchmarkId::from_parameter(id), |b| {
+            b.iter(|| {
+                for access_unit in &access_units {
+                    decoder.decode(access_unit).unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    resolutions,
+    usage_types,
+    rc_modes,
+    complexity_modes,
+    decode
+);
+criterion_main!(benches);
@@ -171,6 +171,27 @@ fn make_unix_path(path_str: &str) -> String {
     }
 }
 
+// cargo-fuzz builds the crate with `--cfg fuzzing` and `-Zsanitizer=...`. Instrument
+// openh264 the same way, without assembly the sanitizers can't see into.
+fn sanitizer_make_args() -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Ok(sanitizers) = env::var("CARGO_CFG_SANITIZE") {
+        for sanitizer in sanitizers.split(',') {
+            flags.push(format!("-fsanitize={}", sanitizer));
+        }
+    }
+    if env::var("CARGO_CFG_FUZZING").is_ok() {
+        flags.push("-fsanitize=fuzzer-no-link".to_owned());
+    }
+    if flags.is_empty() {
+        return Vec::new();
+    }
+    vec![
+        format!("CFLAGS_OPT=-O1 -g -fno-omit-frame-pointer {}", flags.join(" ")),
+        "USE_ASM=No".to_owned(),
+    ]
+}
+
 fn build_library(out_dir_path: &Path, version: &str, dynamic: Option<bool>) -> Library {
     let mut library = Library {
         dynamic: dynamic,
@@ -222,6 +243,7 @@ fn build_library(out_dir_path: &Path, version: &str, dynamic: Option<bool>) -> L
             &format!("PREFIX={}", make_unix_path(prefix_dir_str)),
             make_task,
         ])
+        .args(&sanitizer_make_args())
         .status()
         .expect(&format!(
             "Failed to execute `make {}' for openh264",
@@ -544,7 +566,17 @@ fn find_or_build_library(out_dir_path: &Path) -> Library {
         if cfg!(windows) {
             panic!("feature `build' is currently unimplemented for Windows");
         }
-        return build_library(out_dir_path, full_version, dynamic);
+        let library = build_library(out_dir_path, full_version, dynamic);
+        print_linker_flags(&library);
+        if dynamic == Some(false) {
+            // libopenh264.a is C++ and doesn't pull in the standard library by itself.
+            if cfg!(target_os = "macos") {
+                println!("cargo:rustc-link-lib=dylib=c++");
+            } else {
+                println!("cargo:rustc-link-lib=dylib=stdc++");
+            }
+        }
+        return library;
     }
 
     match (env::var("OPENH264_INCLUDE_PATH"), env::var("OPENH264_LIBRARY_PATH")) {
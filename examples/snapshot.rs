@@ -0,0 +1,39 @@
+//! Saves the first picture of an H.264 Annex B file, the IDR a conforming stream starts
+//! with, as PNG.
+//!
+//! cargo run --example snapshot --features image -- input.264 output.png
+
+extern crate image;
+extern crate openh264_sys;
+
+use openh264_sys::nal::nal_units;
+use openh264_sys::*;
+use std::env;
+use std::fs;
+use std::process::exit;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <input.264> <output.png>", args[0]);
+        exit(2);
+    }
+    let stream = fs::read(&args[1]).expect("Failed to read the input");
+
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    // `decode_frame2` finds access unit boundaries itself, so the stream can go in one
+    // NAL unit at a time.
+    let units = nal_units(&stream).map(|nal| nal.bytes());
+    for data in units.chain(Some(&[][..])) {
+        if let Some(frame) = decoder.decode_frame2(data).unwrap() {
+            frame
+                .to_rgb_image()
+                .save(&args[2])
+                .expect("Failed to write the PNG");
+            return;
+        }
+    }
+    eprintln!("No picture in {}", args[1]);
+    exit(1);
+}
@@ -0,0 +1,56 @@
+//! Arbitrary bytes into `DecodeFrameNoDelay` through `Decoder::decode`.
+//!
+//! The first byte picks how the rest reaches the decoder: as one buffer, or one NAL unit
+//! per call the way a depacketizer hands over network data. Every row of each plane of
+//! each decoded picture is read, concealed ones included, so the sanitizer checks the plane
+//! sizes and strides the wrapper derives from `SBufferInfo` against what openh264 actually
+//! allocated.
+
+#![no_main]
+
+use std::hint::black_box;
+
+use libfuzzer_sys::fuzz_target;
+use openh264_sys::nal::nal_units;
+use openh264_sys::{DecodedFrame, Decoder, SDecodingParam};
+
+fn touch(frame: &DecodedFrame) {
+    let (uv_width, uv_height) = (frame.width.div_ceil(2), frame.height.div_ceil(2));
+    let planes = [
+        (frame.y, frame.y_stride, frame.width, frame.height),
+        (frame.u, frame.uv_stride, uv_width, uv_height),
+        (frame.v, frame.uv_stride, uv_width, uv_height),
+    ];
+    let mut sum = 0u8;
+    for &(plane, stride, width, height) in &planes {
+        for row in 0..height {
+            let start = row * stride;
+            for &sample in &plane[start..start + width] {
+                sum = sum.wrapping_add(sample);
+            }
+        }
+    }
+    black_box(sum);
+    black_box(frame.is_concealed());
+    black_box(frame.to_frame());
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (mode, stream) = match data.split_first() {
+        Some((&mode, stream)) => (mode, stream),
+        None => return,
+    };
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    if mode & 1 == 0 {
+        if let Ok(Some(frame)) = decoder.decode(stream) {
+            touch(&frame);
+        }
+    } else {
+        for nal in nal_units(stream) {
+            if let Ok(Some(frame)) = decoder.decode(nal.bytes()) {
+                touch(&frame);
+            }
+        }
+    }
+});
@@ -0,0 +1,81 @@
+//! Writes a seed corpus for the `decode` target to `corpus/decode`: short streams from
+//! the encoder covering the syntax the decoder has to parse, each as a whole-buffer and a
+//! per-NAL-unit input. Run with `cargo run --bin seed_corpus` from `fuzz/`.
+
+use std::fs;
+use std::path::Path;
+
+use openh264_sys::svc::{SpatialLayer, SvcLayout};
+use openh264_sys::testsrc::{Pattern, TestSource};
+use openh264_sys::*;
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 48;
+const FRAMES: usize = 6;
+
+/// Adjusts single-layer `WIDTH`x`HEIGHT` parameters before `initialize`.
+type Configure = fn(&mut SEncParamExt);
+
+fn encode(pattern: Pattern, configure: Configure) -> Vec<u8> {
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = encoder.default_params().unwrap();
+    param.iUsageType = CAMERA_VIDEO_REAL_TIME;
+    SvcLayout::new(vec![SpatialLayer::new(
+        WIDTH as u32,
+        HEIGHT as u32,
+        30.0,
+        500_000,
+    )])
+    .apply(&mut param)
+    .unwrap();
+    configure(&mut param);
+    encoder.initialize(&param).unwrap();
+
+    let mut stream = Vec::new();
+    for frame in TestSource::new(pattern, WIDTH, HEIGHT).take(FRAMES) {
+        let encoded = encoder.encode(&frame.as_yuv().unwrap()).unwrap();
+        stream.extend(encoded.to_bytes());
+    }
+    stream
+}
+
+fn main() {
+    let seeds: [(&str, Pattern, Configure); 6] = [
+        ("baseline", Pattern::Gradient, |_| {}),
+        ("cabac", Pattern::ColorBars, |param| {
+            param.iEntropyCodingModeFlag = 1;
+            param.sSpatialLayers[0].uiProfileIdc = PRO_HIGH;
+        }),
+        ("slices", Pattern::ScrollingText, |param| {
+            param.sSpatialLayers[0].sSliceArgument.uiSliceMode = SM_FIXEDSLCNUM_SLICE;
+            param.sSpatialLayers[0].sSliceArgument.uiSliceNum = 3;
+        }),
+        ("temporal", Pattern::Gradient, |param| {
+            param.iTemporalLayerNum = 3;
+            param.bPrefixNalAddingCtrl = true;
+        }),
+        ("spatial", Pattern::ColorBars, |param| {
+            let mut layout = SvcLayout::new(vec![
+                SpatialLayer::new(WIDTH as u32 / 2, HEIGHT as u32 / 2, 30.0, 200_000),
+                SpatialLayer::new(WIDTH as u32, HEIGHT as u32, 30.0, 500_000),
+            ]);
+            layout.temporal_layers = 2;
+            layout.apply(param).unwrap();
+        }),
+        ("ltr", Pattern::SceneCuts { interval: 2 }, |param| {
+            param.bEnableLongTermReference = true;
+            param.iLTRRefNum = 1;
+        }),
+    ];
+
+    let directory = Path::new("corpus/decode");
+    fs::create_dir_all(directory).unwrap();
+    for &(name, pattern, configure) in &seeds {
+        let stream = encode(pattern, configure);
+        for &(mode, suffix) in &[(0u8, "whole"), (1u8, "nal")] {
+            let mut input = vec![mode];
+            input.extend_from_slice(&stream);
+            fs::write(directory.join(format!("{}-{}.264", name, suffix)), input).unwrap();
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! `futures` front ends that run an `Encoder` or `Decoder` on a dedicated thread, so the
+//! blocking openh264 calls never stall an async runtime.
+//!
+//! Input goes in through `Sink` and output comes out through `Stream`, over bounded
+//! channels: `poll_ready` stays pending while the worker is that far behind, and the worker
+//! waits while that much output is unread. A `futures` channel holds `buffer` items plus
+//! one per sender, and each of the two has a single sender, so both hold `buffer + 1`.
+//!
+//! Closing the sink lets the worker finish the queued input, destroy the native instance
+//! and then end the stream. Dropping the front end cancels instead: the worker stops after
+//! the call in progress and destroys the instance all the same.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures::channel::mpsc::{self, Receiver, SendError, Sender};
+use futures::executor::{block_on, block_on_stream};
+use futures::{Sink, SinkExt, Stream};
+
+use decoder::Decoder;
+use encoder::{EncodedFrame, Encoder};
+use error::{Error, Result};
+use frame::Frame;
+use {SDecodingParam, SEncParamExt};
+
+/// `Sink<Frame>` and `Stream<Item = Result<EncodedFrame>>` over an encoder thread.
+pub struct AsyncEncoder {
+    input: Sender<Frame>,
+    output: Receiver<Result<EncodedFrame>>,
+}
+
+/// `Sink<Vec<u8>>` of access units and `Stream<Item = Result<Frame>>` of decoded pictures
+/// over a decoder thread.
+pub struct AsyncDecoder {
+    input: Sender<Vec<u8>>,
+    output: Receiver<Result<Frame>>,
+}
+
+/// Runs `process` on every input item on a new thread until the input closes or the
+/// output is dropped, checking for the latter before each item. `instance` is dropped
+/// before `output`, so the stream only ends once the native instance is destroyed.
+fn spawn_worker<C, I, O, F>(
+    buffer: usize,
+    mut instance: C,
+    mut process: F,
+) -> (Sender<I>, Receiver<Result<O>>)
+where
+    C: Send + 'static,
+    I: Send + 'static,
+    O: Send + 'static,
+    F: FnMut(&mut C, I) -> Option<Result<O>> + Send + 'static,
+{
+    let (input, jobs) = mpsc::channel(buffer);
+    let (mut results, output) = mpsc::channel(buffer);
+    thread::spawn(move || {
+        for job in block_on_stream(jobs) {
+            if results.is_closed() {
+                break;
+            }
+            let result = match process(&mut instance, job) {
+                Some(result) => result,
+                None => continue,
+            };
+            if block_on(results.send(result)).is_err() {
+                break;
+            }
+        }
+        drop(instance);
+        drop(results);
+    });
+    (input, output)
+}
+
+fn disconnected(_: SendError) -> Error {
+    Error::Disconnected
+}
+
+impl AsyncEncoder {
+    /// Creates and initializes the encoder on the calling thread, then moves it to its
+    /// worker.
+    pub fn new(param: &SEncParamExt, buffer: usize) -> Result<AsyncEncoder> {
+        let mut encoder = Encoder::new()?;
+        encoder.initialize(param)?;
+        let (input, output) = spawn_worker(buffer, encoder, |encoder, frame: Frame| {
+            Some(frame.as_yuv().and_then(|frame| encoder.encode(&frame)))
+        });
+        Ok(AsyncEncoder { input, output })
+    }
+}
+
+impl AsyncDecoder {
+    /// Creates and initializes the decoder on the calling thread, then moves it to its
+    /// worker. Access units that complete no picture produce no stream item.
+    pub fn new(param: &SDecodingParam, buffer: usize) -> Result<AsyncDecoder> {
+        let mut decoder = Decoder::new()?;
+        decoder.initialize(param)?;
+        let (input, output) = spawn_worker(buffer, decoder, |decoder, data: Vec<u8>| {
+            let decoded = decoder.decode(&data);
+            decoded
+                .map(|frame| frame.map(|frame| frame.to_frame()))
+                .transpose()
+        });
+        Ok(AsyncDecoder { input, output })
+    }
+}
+
+impl Sink<Frame> for AsyncEncoder {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.input.poll_ready(cx).map_err(disconnected)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, frame: Frame) -> Result<()> {
+        self.input.start_send(frame).map_err(disconnected)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.input)
+            .poll_flush(cx)
+            .map_err(disconnected)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.input)
+            .poll_close(cx)
+            .map_err(disconnected)
+    }
+}
+
+impl Stream for AsyncEncoder {
+    type Item = Result<EncodedFrame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.output).poll_next(cx)
+    }
+}
+
+impl Sink<Vec<u8>> for AsyncDecoder {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.input.poll_ready(cx).map_err(disconnected)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, data: Vec<u8>) -> Result<()> {
+        self.input.start_send(data).map_err(disconnected)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.input)
+            .poll_flush(cx)
+            .map_err(disconnected)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        Pin::new(&mut self.input)
+            .poll_close(cx)
+            .map_err(disconnected)
+    }
+}
+
+impl Stream for AsyncDecoder {
+    type Item = Result<Frame>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.output).poll_next(cx)
+    }
+}
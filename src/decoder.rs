@@ -0,0 +1,376 @@
+use std::os::raw::{c_int, c_void};
+use std::ptr::{null, null_mut};
+use std::slice::from_raw_parts;
+
+use error::{check, Error, Result};
+use frame::Frame;
+use ltr::{LtrMarkingFeedback, LtrRecoveryRequest};
+use option::{self, DecoderOption, OptionValue};
+#[cfg(feature = "tracing")]
+use span;
+use stats::DecoderStats;
+#[cfg(feature = "log")]
+use trace;
+#[cfg(feature = "log")]
+use WelsTraceCallback;
+use {
+    dsErrorFree, ISVCDecoder, SBufferInfo, SDecodingParam, WelsCreateDecoder, WelsDestroyDecoder,
+    DECODER_OPTION, DECODING_STATE,
+};
+
+/// Signature shared by `DecodeFrameNoDelay` and `DecodeFrame2`.
+type DecodeFn = unsafe extern "C" fn(
+    *mut ISVCDecoder,
+    *const u8,
+    c_int,
+    *mut *mut u8,
+    *mut SBufferInfo,
+) -> DECODING_STATE;
+
+/// Owned `ISVCDecoder`, destroyed with `WelsDestroyDecoder` on drop.
+pub struct Decoder {
+    raw: *mut ISVCDecoder,
+    #[cfg(feature = "log")]
+    trace: Box<trace::TraceContext>,
+}
+
+// Same reasoning as for `Encoder`: no thread affinity, but no concurrent calls either.
+unsafe impl Send for Decoder {}
+
+/// I420 picture owned by the decoder. It borrows the decoder because openh264 reuses the
+/// buffers on the next decode call.
+pub struct DecodedFrame<'a> {
+    /// Decoding state of the call that returned the picture. Anything but `dsErrorFree`
+    /// means openh264 concealed errors, e.g. a lost reference, and the picture may show
+    /// artifacts.
+    pub state: DECODING_STATE,
+    pub width: usize,
+    pub height: usize,
+    pub timestamp: u64,
+    pub y: &'a [u8],
+    pub u: &'a [u8],
+    pub v: &'a [u8],
+    pub y_stride: usize,
+    pub uv_stride: usize,
+}
+
+impl<'a> DecodedFrame<'a> {
+    unsafe fn from_raw(state: DECODING_STATE, info: &SBufferInfo) -> DecodedFrame<'a> {
+        let buffer = info.UsrData.sSystemBuffer;
+        let width = buffer.iWidth as usize;
+        let height = buffer.iHeight as usize;
+        let y_stride = buffer.iStride[0] as usize;
+        let uv_stride = buffer.iStride[1] as usize;
+        let uv_height = height.div_ceil(2);
+        DecodedFrame {
+            state,
+            width,
+            height,
+            timestamp: info.uiOutYuvTimeStamp,
+            y: from_raw_parts(info.pDst[0], y_stride * height),
+            u: from_raw_parts(info.pDst[1], uv_stride * uv_height),
+            v: from_raw_parts(info.pDst[2], uv_stride * uv_height),
+            y_stride,
+            uv_stride,
+        }
+    }
+
+    /// Whether openh264 reported errors for this picture and concealed them.
+    pub fn is_concealed(&self) -> bool {
+        self.state != dsErrorFree
+    }
+
+    /// Copies the picture into an owned, tightly packed `Frame` that outlives the next
+    /// decode call.
+    pub fn to_frame(&self) -> Frame {
+        let uv_width = self.width.div_ceil(2);
+        let uv_height = self.height.div_ceil(2);
+        let mut frame = Frame {
+            width: self.width,
+            height: self.height,
+            timestamp: self.timestamp as i64,
+            planes: [
+                Vec::with_capacity(self.width * self.height),
+                Vec::with_capacity(uv_width * uv_height),
+                Vec::with_capacity(uv_width * uv_height),
+            ],
+        };
+        for row in self.y.chunks(self.y_stride).take(self.height) {
+            frame.planes[0].extend_from_slice(&row[..self.width]);
+        }
+        for row in self.u.chunks(self.uv_stride).take(uv_height) {
+            frame.planes[1].extend_from_slice(&row[..uv_width]);
+        }
+        for row in self.v.chunks(self.uv_stride).take(uv_height) {
+            frame.planes[2].extend_from_slice(&row[..uv_width]);
+        }
+        frame
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Result<Decoder> {
+        let mut raw = null_mut();
+        if unsafe { WelsCreateDecoder(&mut raw) } != 0 || raw.is_null() {
+            return Err(Error::Create);
+        }
+        #[cfg_attr(not(feature = "log"), allow(unused_mut))]
+        let mut decoder = Decoder {
+            raw,
+            #[cfg(feature = "log")]
+            trace: trace::TraceContext::new("decoder"),
+        };
+        #[cfg(feature = "log")]
+        decoder.install_trace_callback()?;
+        Ok(decoder)
+    }
+
+    #[cfg(feature = "log")]
+    fn install_trace_callback(&mut self) -> Result<()> {
+        let mut context = self.trace.as_ptr();
+        let mut callback: WelsTraceCallback = Some(trace::trace_callback);
+        unsafe {
+            self.set_option_unchecked(option::decoder::TRACE_CALLBACK_CONTEXT, &mut context)?;
+            self.set_option_unchecked(option::decoder::TRACE_CALLBACK, &mut callback)?;
+        }
+        self.set_option(option::decoder::TRACE_LEVEL, trace::wels_log_level())
+    }
+
+    /// Target of the `log` records this instance's trace output is forwarded to.
+    #[cfg(feature = "log")]
+    pub fn log_target(&self) -> &str {
+        &self.trace.target
+    }
+
+    pub fn as_raw(&self) -> *mut ISVCDecoder {
+        self.raw
+    }
+
+    pub fn initialize(&mut self, param: &SDecodingParam) -> Result<()> {
+        check(unsafe { (**self.raw).Initialize.unwrap()(self.raw, param) })
+    }
+
+    /// Passes `value` to `SetOption`.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the pointee type openh264 expects for `option`.
+    pub unsafe fn set_option_raw<T>(
+        &mut self,
+        option: DECODER_OPTION,
+        value: &mut T,
+    ) -> Result<()> {
+        check((**self.raw).SetOption.unwrap()(
+            self.raw,
+            option,
+            value as *mut T as *mut c_void,
+        ))
+    }
+
+    /// Passes `value` to `GetOption`.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the pointee type openh264 expects for `option`.
+    pub unsafe fn get_option_raw<T>(&self, option: DECODER_OPTION, value: &mut T) -> Result<()> {
+        check((**self.raw).GetOption.unwrap()(
+            self.raw,
+            option,
+            value as *mut T as *mut c_void,
+        ))
+    }
+
+    /// Decodes one access unit with `DecodeFrameNoDelay`. Returns `None` when the input
+    /// produced no picture, e.g. parameter sets only. A picture is returned even when
+    /// openh264 had to conceal errors, see `DecodedFrame::state`; errors without a picture
+    /// are `Error::Decoding`.
+    pub fn decode(&mut self, data: &[u8]) -> Result<Option<DecodedFrame<'_>>> {
+        self.decode_with_timestamp(data, 0)
+    }
+
+    /// Like `decode`, passing `timestamp` as `uiInBsTimeStamp`. openh264 hands it back as
+    /// the `timestamp` of the picture the access unit completes.
+    pub fn decode_with_timestamp(
+        &mut self,
+        data: &[u8],
+        timestamp: u64,
+    ) -> Result<Option<DecodedFrame<'_>>> {
+        #[cfg(feature = "tracing")]
+        let span = span::decode_frame_no_delay(data.len());
+
+        let decode_frame_no_delay = unsafe { (**self.raw).DecodeFrameNoDelay.unwrap() };
+        let (state, info) = {
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+            self.call_decode(decode_frame_no_delay, data, timestamp)
+        };
+
+        #[cfg(feature = "tracing")]
+        self.record_span(&span, state, &info);
+        self.decoded_frame(state, &info)
+    }
+
+    /// Decodes one access unit with `DecodeFrame2`, which may hold a picture back until a
+    /// later call. An empty `data` flushes the next held-back picture.
+    pub fn decode_frame2(&mut self, data: &[u8]) -> Result<Option<DecodedFrame<'_>>> {
+        #[cfg(feature = "tracing")]
+        let span = span::decode_frame2(data.len());
+
+        let decode_frame2 = unsafe { (**self.raw).DecodeFrame2.unwrap() };
+        let (state, info) = {
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+            self.call_decode(decode_frame2, data, 0)
+        };
+
+        #[cfg(feature = "tracing")]
+        self.record_span(&span, state, &info);
+        self.decoded_frame(state, &info)
+    }
+
+    fn call_decode(
+        &mut self,
+        decode: DecodeFn,
+        data: &[u8],
+        timestamp: u64,
+    ) -> (DECODING_STATE, SBufferInfo) {
+        let mut dst = [null_mut(); 3];
+        let mut info = SBufferInfo {
+            uiInBsTimeStamp: timestamp,
+            ..SBufferInfo::default()
+        };
+        let src = if data.is_empty() {
+            null()
+        } else {
+            data.as_ptr()
+        };
+        let state = unsafe {
+            decode(
+                self.raw,
+                src,
+                data.len() as c_int,
+                dst.as_mut_ptr(),
+                &mut info,
+            )
+        };
+        (state, info)
+    }
+
+    fn decoded_frame(
+        &self,
+        state: DECODING_STATE,
+        info: &SBufferInfo,
+    ) -> Result<Option<DecodedFrame<'_>>> {
+        if info.iBufferStatus == 1 {
+            return Ok(Some(unsafe { DecodedFrame::from_raw(state, info) }));
+        }
+        if state != dsErrorFree {
+            return Err(Error::Decoding(state));
+        }
+        Ok(None)
+    }
+
+    #[cfg(feature = "tracing")]
+    fn record_span(&self, span: &::tracing::Span, state: DECODING_STATE, info: &SBufferInfo) {
+        let frame_size = if info.iBufferStatus == 1 {
+            let buffer = unsafe { info.UsrData.sSystemBuffer };
+            Some((buffer.iWidth as usize, buffer.iHeight as usize))
+        } else {
+            None
+        };
+        span::record_decoded(span, state, frame_size, || {
+            self.stats().ok().map(|stats| stats.average_luma_qp)
+        });
+    }
+
+    pub fn set_option<T: OptionValue>(&mut self, option: DecoderOption<T>, value: T) -> Result<()> {
+        let mut value = value;
+        unsafe { self.set_option_raw(option.id(), &mut value) }
+    }
+
+    pub fn get_option<T: OptionValue>(&self, option: DecoderOption<T>) -> Result<T> {
+        self.get_option_with(option, T::default())
+    }
+
+    /// Like `get_option`, but starts from `initial` instead of `T::default()`.
+    pub fn get_option_with<T: OptionValue>(
+        &self,
+        option: DecoderOption<T>,
+        initial: T,
+    ) -> Result<T> {
+        let mut value = initial;
+        unsafe { self.get_option_raw(option.id(), &mut value)? };
+        Ok(value)
+    }
+
+    /// Passes `value` to `SetOption` for options whose payload holds pointers.
+    ///
+    /// # Safety
+    ///
+    /// Every pointer reachable from `value` must stay valid for as long as the decoder may
+    /// use it, which for trace callbacks is the lifetime of the decoder.
+    pub unsafe fn set_option_unchecked<T>(
+        &mut self,
+        option: DecoderOption<T>,
+        value: &mut T,
+    ) -> Result<()> {
+        self.set_option_raw(option.id(), value)
+    }
+
+    pub fn stats(&self) -> Result<DecoderStats> {
+        self.get_option(option::decoder::GET_STATISTICS)
+            .map(DecoderStats::from)
+    }
+
+    pub fn frame_num(&self) -> Result<i32> {
+        self.get_option(option::decoder::FRAME_NUM)
+    }
+
+    pub fn idr_pic_id(&self) -> Result<u32> {
+        self.get_option(option::decoder::IDR_PIC_ID)
+            .map(|id| id as u32)
+    }
+
+    /// Whether the last decoded frame carried a long-term reference marking.
+    pub fn ltr_marking_flag(&self) -> Result<bool> {
+        self.get_option(option::decoder::LTR_MARKING_FLAG)
+            .map(|flag| flag != 0)
+    }
+
+    pub fn ltr_marked_frame_num(&self) -> Result<i32> {
+        self.get_option(option::decoder::LTR_MARKED_FRAME_NUM)
+    }
+
+    /// Builds the marking feedback the encoder expects after the last decoded frame, or
+    /// `None` when that frame wasn't marked as a long-term reference.
+    pub fn ltr_marking_feedback(&self) -> Result<Option<LtrMarkingFeedback>> {
+        if !self.ltr_marking_flag()? {
+            return Ok(None);
+        }
+        Ok(Some(LtrMarkingFeedback {
+            success: true,
+            idr_pic_id: self.idr_pic_id()?,
+            ltr_frame_num: self.ltr_marked_frame_num()?,
+            layer_id: 0,
+        }))
+    }
+
+    /// Builds an LTR recovery request for a loss detected after `last_correct_frame_num`,
+    /// using the decoder's current IDR picture id and frame number.
+    pub fn ltr_recovery_request(&self, last_correct_frame_num: i32) -> Result<LtrRecoveryRequest> {
+        Ok(LtrRecoveryRequest::ltr(
+            self.idr_pic_id()?,
+            last_correct_frame_num,
+            self.frame_num()?,
+        ))
+    }
+}
+
+impl Drop for Decoder {
+    fn drop(&mut self) {
+        unsafe {
+            (**self.raw).Uninitialize.unwrap()(self.raw);
+            WelsDestroyDecoder(self.raw);
+        }
+    }
+}
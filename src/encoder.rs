@@ -0,0 +1,596 @@
+use std::os::raw::{c_int, c_void};
+use std::ptr::null_mut;
+use std::slice::from_raw_parts;
+use std::time::Duration;
+
+use error::{check, Error, Result};
+use keyframe::{self, KeyframeRequester};
+use ltr::{LtrMarkingFeedback, LtrRecoveryRequest};
+use nal;
+use option::{self, EncoderOption, OptionValue};
+use rate_control::RateControl;
+#[cfg(feature = "tracing")]
+use span;
+use stats::EncoderStats;
+#[cfg(feature = "log")]
+use trace;
+use yuv::YuvFrameRef;
+#[cfg(feature = "log")]
+use WelsTraceCallback;
+use {
+    videoFormatI420, videoFrameTypeIDR, EVideoFrameType, ISVCEncoder, SBitrateInfo, SEncParamExt,
+    SFrameBSInfo, SSourcePicture, WelsCreateSVCEncoder, WelsDestroySVCEncoder, ENCODER_OPTION,
+    LAYER_NUM, MAX_SPATIAL_LAYER_NUM, SPATIAL_LAYER_0, SPATIAL_LAYER_ALL, VIDEO_CODING_LAYER,
+};
+
+/// Owned `ISVCEncoder`, destroyed with `WelsDestroySVCEncoder` on drop.
+pub struct Encoder {
+    raw: *mut ISVCEncoder,
+    prepend_parameter_sets: bool,
+    keyframe_requests: KeyframeRequester,
+    last_timestamp: Option<i64>,
+    #[cfg(feature = "log")]
+    trace: Box<trace::TraceContext>,
+}
+
+// openh264 keeps all encoder state behind the instance pointer, uses no thread-local
+// storage and joins its own slice threads before `EncodeFrame` returns, so an instance may
+// move between threads. Calls must not overlap, which raw pointers keep `Encoder` `!Sync`
+// for.
+unsafe impl Send for Encoder {}
+
+/// Layer selector for `SBitrateInfo::iLayer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layer {
+    /// One spatial layer, `0..MAX_SPATIAL_LAYER_NUM`.
+    Spatial(usize),
+    /// The whole stream; openh264 splits the value across spatial layers.
+    All,
+}
+
+impl Layer {
+    fn to_bitrate_info(self, bitrate: i32) -> Result<SBitrateInfo> {
+        let layer = match self {
+            Layer::Spatial(index) if index < MAX_SPATIAL_LAYER_NUM as usize => {
+                SPATIAL_LAYER_0 + index as LAYER_NUM
+            }
+            Layer::Spatial(index) => {
+                return Err(Error::InvalidArgument(format!(
+                    "Spatial layer {} is out of range 0..{}",
+                    index, MAX_SPATIAL_LAYER_NUM
+                )))
+            }
+            Layer::All => SPATIAL_LAYER_ALL,
+        };
+        Ok(SBitrateInfo {
+            iLayer: layer,
+            iBitrate: bitrate,
+        })
+    }
+}
+
+/// One layer of an encoded frame, copied out of `SLayerBSInfo`.
+#[derive(Debug, Clone)]
+pub struct EncodedLayer {
+    pub spatial_id: u8,
+    pub temporal_id: u8,
+    pub quality_id: u8,
+    pub frame_type: EVideoFrameType,
+    /// `false` for the layer holding the SPS/PPS of an IDR frame.
+    pub video_coding: bool,
+    /// NAL units including their start codes, in bitstream order.
+    pub nals: Vec<Vec<u8>>,
+}
+
+/// Owned copy of the `SFrameBSInfo` filled by `EncodeFrame`.
+#[derive(Debug, Clone)]
+pub struct EncodedFrame {
+    pub frame_type: EVideoFrameType,
+    pub timestamp: i64,
+    pub layers: Vec<EncodedLayer>,
+}
+
+impl EncodedFrame {
+    unsafe fn from_raw(info: &SFrameBSInfo) -> EncodedFrame {
+        let mut layers = Vec::new();
+        for layer_info in &info.sLayerInfo[..info.iLayerNum as usize] {
+            let mut nals = Vec::new();
+            let mut offset = 0;
+            for i in 0..layer_info.iNalCount {
+                let length = *layer_info.pNalLengthInByte.offset(i as isize) as usize;
+                nals.push(from_raw_parts(layer_info.pBsBuf.add(offset), length).to_vec());
+                offset += length;
+            }
+            layers.push(EncodedLayer {
+                spatial_id: layer_info.uiSpatialId,
+                temporal_id: layer_info.uiTemporalId,
+                quality_id: layer_info.uiQualityId,
+                frame_type: layer_info.eFrameType,
+                video_coding: u32::from(layer_info.uiLayerType) == VIDEO_CODING_LAYER,
+                nals,
+            });
+        }
+        EncodedFrame {
+            frame_type: info.eFrameType,
+            timestamp: info.uiTimeStamp,
+            layers,
+        }
+    }
+
+    /// Whether this access unit is an IDR, which decoding can start from. The frame after a
+    /// keyframe request is one unless rate control skipped it, in which case the request
+    /// carries over to the next frame that is encoded.
+    pub fn is_idr(&self) -> bool {
+        self.frame_type == videoFrameTypeIDR
+    }
+
+    /// Total size of all NAL units in bytes.
+    pub fn size(&self) -> usize {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.nals.iter())
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// Concatenates every NAL unit of every layer into one Annex B access unit.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.select(|_| true)
+    }
+
+    /// The access unit of one simulcast stream: the layers of `spatial_id` with temporal id
+    /// up to `max_temporal_id`, plus that spatial layer's parameter sets. Use with
+    /// `SvcLayout::simulcast`, where every spatial layer decodes on its own.
+    pub fn simulcast_bytes(&self, spatial_id: u8, max_temporal_id: u8) -> Vec<u8> {
+        self.select(|layer| {
+            layer.spatial_id == spatial_id
+                && (!layer.video_coding || layer.temporal_id <= max_temporal_id)
+        })
+    }
+
+    /// The access unit of an SVC operating point: every layer with spatial id up to
+    /// `max_spatial_id` and temporal id up to `max_temporal_id`, plus the parameter sets
+    /// those layers depend on.
+    pub fn svc_bytes(&self, max_spatial_id: u8, max_temporal_id: u8) -> Vec<u8> {
+        self.select(|layer| {
+            layer.spatial_id <= max_spatial_id
+                && (!layer.video_coding || layer.temporal_id <= max_temporal_id)
+        })
+    }
+
+    fn select<F: Fn(&EncodedLayer) -> bool>(&self, keep: F) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for layer in self.layers.iter().filter(|layer| keep(layer)) {
+            for nal in &layer.nals {
+                bytes.extend_from_slice(nal);
+            }
+        }
+        bytes
+    }
+
+    fn has_parameter_sets(&self) -> bool {
+        self.layers
+            .iter()
+            .flat_map(|layer| layer.nals.iter())
+            .any(|nal| {
+                matches!(
+                    nal_type(nal),
+                    Some(nal::NAL_SPS) | Some(nal::NAL_SUBSET_SPS)
+                )
+            })
+    }
+}
+
+fn nal_type(nal: &[u8]) -> Option<u8> {
+    nal::nal_units(nal).next().map(|unit| unit.nal_type())
+}
+
+/// SPS and PPS NAL units of an encoder's current configuration, start codes included,
+/// each with the spatial layer it belongs to. Enhancement layers of an SVC stream have a
+/// subset SPS, which counts as an SPS here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParameterSets {
+    pub sps: Vec<(u8, Vec<u8>)>,
+    pub pps: Vec<(u8, Vec<u8>)>,
+}
+
+impl ParameterSets {
+    fn from_frame(frame: &EncodedFrame) -> ParameterSets {
+        let mut sets = ParameterSets {
+            sps: Vec::new(),
+            pps: Vec::new(),
+        };
+        for layer in &frame.layers {
+            for nal in &layer.nals {
+                match nal_type(nal) {
+                    Some(nal::NAL_SPS) | Some(nal::NAL_SUBSET_SPS) => {
+                        sets.sps.push((layer.spatial_id, nal.clone()))
+                    }
+                    Some(nal::NAL_PPS) => sets.pps.push((layer.spatial_id, nal.clone())),
+                    _ => {}
+                }
+            }
+        }
+        sets
+    }
+
+    /// The parameter sets of one spatial layer.
+    pub fn layer(&self, spatial_id: u8) -> ParameterSets {
+        let of_layer = |sets: &[(u8, Vec<u8>)]| {
+            sets.iter()
+                .filter(|set| set.0 == spatial_id)
+                .cloned()
+                .collect()
+        };
+        ParameterSets {
+            sps: of_layer(&self.sps),
+            pps: of_layer(&self.pps),
+        }
+    }
+
+    /// Every SPS as one Annex B buffer.
+    pub fn sps_bytes(&self) -> Vec<u8> {
+        self.sps
+            .iter()
+            .flat_map(|set| set.1.iter().cloned())
+            .collect()
+    }
+
+    /// Every PPS as one Annex B buffer.
+    pub fn pps_bytes(&self) -> Vec<u8> {
+        self.pps
+            .iter()
+            .flat_map(|set| set.1.iter().cloned())
+            .collect()
+    }
+
+    /// SPSs followed by PPSs, ready to go in front of an access unit.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.sps_bytes();
+        bytes.extend(self.pps_bytes());
+        bytes
+    }
+
+    /// One non-VCL layer per NAL unit, the way openh264 lays out parameter sets.
+    fn to_layers(&self, frame_type: EVideoFrameType) -> Vec<EncodedLayer> {
+        self.sps
+            .iter()
+            .chain(&self.pps)
+            .map(|&(spatial_id, ref nal)| EncodedLayer {
+                spatial_id,
+                temporal_id: 0,
+                quality_id: 0,
+                frame_type,
+                video_coding: false,
+                nals: vec![nal.clone()],
+            })
+            .collect()
+    }
+}
+
+impl Encoder {
+    pub fn new() -> Result<Encoder> {
+        let mut raw = null_mut();
+        if unsafe { WelsCreateSVCEncoder(&mut raw) } != 0 || raw.is_null() {
+            return Err(Error::Create);
+        }
+        #[cfg_attr(not(feature = "log"), allow(unused_mut))]
+        let mut encoder = Encoder {
+            raw,
+            prepend_parameter_sets: false,
+            keyframe_requests: KeyframeRequester::default(),
+            last_timestamp: None,
+            #[cfg(feature = "log")]
+            trace: trace::TraceContext::new("encoder"),
+        };
+        #[cfg(feature = "log")]
+        encoder.install_trace_callback()?;
+        Ok(encoder)
+    }
+
+    #[cfg(feature = "log")]
+    fn install_trace_callback(&mut self) -> Result<()> {
+        let mut context = self.trace.as_ptr();
+        let mut callback: WelsTraceCallback = Some(trace::trace_callback);
+        unsafe {
+            self.set_option_unchecked(option::encoder::TRACE_CALLBACK_CONTEXT, &mut context)?;
+            self.set_option_unchecked(option::encoder::TRACE_CALLBACK, &mut callback)?;
+        }
+        self.set_option(option::encoder::TRACE_LEVEL, trace::wels_log_level())
+    }
+
+    /// Target of the `log` records this instance's trace output is forwarded to.
+    #[cfg(feature = "log")]
+    pub fn log_target(&self) -> &str {
+        &self.trace.target
+    }
+
+    pub fn as_raw(&self) -> *mut ISVCEncoder {
+        self.raw
+    }
+
+    pub fn default_params(&self) -> Result<SEncParamExt> {
+        let mut param = SEncParamExt::default();
+        check(unsafe { (**self.raw).GetDefaultParams.unwrap()(self.raw, &mut param) })?;
+        Ok(param)
+    }
+
+    /// Calls `InitializeExt` and selects I420 input.
+    pub fn initialize(&mut self, param: &SEncParamExt) -> Result<()> {
+        check(unsafe { (**self.raw).InitializeExt.unwrap()(self.raw, param) })?;
+        self.set_option(option::encoder::DATAFORMAT, videoFormatI420 as c_int)
+    }
+
+    /// Passes `value` to `SetOption`.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the pointee type openh264 expects for `option`.
+    pub unsafe fn set_option_raw<T>(
+        &mut self,
+        option: ENCODER_OPTION,
+        value: &mut T,
+    ) -> Result<()> {
+        check((**self.raw).SetOption.unwrap()(
+            self.raw,
+            option,
+            value as *mut T as *mut c_void,
+        ))
+    }
+
+    /// Passes `value` to `GetOption`.
+    ///
+    /// # Safety
+    ///
+    /// `T` must be the pointee type openh264 expects for `option`.
+    pub unsafe fn get_option_raw<T>(&self, option: ENCODER_OPTION, value: &mut T) -> Result<()> {
+        check((**self.raw).GetOption.unwrap()(
+            self.raw,
+            option,
+            value as *mut T as *mut c_void,
+        ))
+    }
+
+    pub fn set_option<T: OptionValue>(&mut self, option: EncoderOption<T>, value: T) -> Result<()> {
+        let mut value = value;
+        unsafe { self.set_option_raw(option.id(), &mut value) }
+    }
+
+    pub fn get_option<T: OptionValue>(&self, option: EncoderOption<T>) -> Result<T> {
+        self.get_option_with(option, T::default())
+    }
+
+    /// Like `get_option`, but starts from `initial` instead of `T::default()`, for options
+    /// that read part of the payload first, e.g. `iLayer` of `BITRATE`.
+    pub fn get_option_with<T: OptionValue>(
+        &self,
+        option: EncoderOption<T>,
+        initial: T,
+    ) -> Result<T> {
+        let mut value = initial;
+        unsafe { self.get_option_raw(option.id(), &mut value)? };
+        Ok(value)
+    }
+
+    /// Passes `value` to `SetOption` for options whose payload holds pointers.
+    ///
+    /// # Safety
+    ///
+    /// Every pointer reachable from `value` must stay valid for as long as the encoder may
+    /// use it, which for trace callbacks and paths is the lifetime of the encoder.
+    pub unsafe fn set_option_unchecked<T>(
+        &mut self,
+        option: EncoderOption<T>,
+        value: &mut T,
+    ) -> Result<()> {
+        self.set_option_raw(option.id(), value)
+    }
+
+    pub fn force_intra_frame(&mut self, idr: bool) -> Result<()> {
+        check(unsafe { (**self.raw).ForceIntraFrame.unwrap()(self.raw, idr) })
+    }
+
+    /// Makes the next encoded frame an IDR.
+    pub fn request_keyframe(&self) {
+        self.keyframe_requests.request();
+    }
+
+    /// A handle other threads can request keyframes through while this encoder is busy.
+    pub fn keyframe_requester(&self) -> KeyframeRequester {
+        self.keyframe_requests.clone()
+    }
+
+    /// Changes the periodic IDR interval in frames, `0` to turn periodic IDRs off.
+    pub fn set_idr_interval(&mut self, frames: u32) -> Result<()> {
+        let frames = keyframe::checked_interval(frames)?;
+        self.set_option(option::encoder::IDR_INTERVAL, frames)
+    }
+
+    /// Encodes one picture.
+    ///
+    /// # Safety
+    ///
+    /// `pic.pData` and `pic.iStride` must describe readable planes of `pic.iPicWidth` x
+    /// `pic.iPicHeight` pixels in `pic.iColorFormat`.
+    pub unsafe fn encode_frame(&mut self, pic: &SSourcePicture) -> Result<EncodedFrame> {
+        #[cfg(feature = "tracing")]
+        let span = span::encode_frame(pic.iPicWidth, pic.iPicHeight);
+
+        if self.keyframe_requests.take() {
+            self.force_intra_frame(true)?;
+        }
+        let mut info = SFrameBSInfo::default();
+        check({
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+            (**self.raw).EncodeFrame.unwrap()(self.raw, pic, &mut info)
+        })?;
+        let mut frame = EncodedFrame::from_raw(&info);
+        if self.prepend_parameter_sets && frame.is_idr() && !frame.has_parameter_sets() {
+            let sets = self.parameter_sets()?;
+            frame.layers.splice(0..0, sets.to_layers(frame.frame_type));
+        }
+
+        #[cfg(feature = "tracing")]
+        span::record_encoded(&span, &frame, || {
+            self.stats().ok().map(|stats| stats.average_frame_qp)
+        });
+        Ok(frame)
+    }
+
+    /// Encodes one picture straight from the borrowed planes, without copying them.
+    pub fn encode(&mut self, frame: &YuvFrameRef) -> Result<EncodedFrame> {
+        unsafe { self.encode_frame(&frame.picture()) }
+    }
+
+    /// Encodes one picture captured at `time`, e.g. from `timestamp::Timeline`, instead of at
+    /// `frame.timestamp`. The time is truncated to the millisecond `uiTimeStamp` that
+    /// `RC_TIMESTAMP_MODE` paces by and that the result carries. Each call has to be at
+    /// least a millisecond past the previous one, or it fails with
+    /// `Error::NonMonotonicTimestamp` and the frame isn't encoded.
+    pub fn encode_at(&mut self, frame: &YuvFrameRef, time: Duration) -> Result<EncodedFrame> {
+        let millis = time.as_millis();
+        if millis > i64::MAX as u128 {
+            return Err(Error::InvalidArgument(format!(
+                "Timestamp {:?} is out of range",
+                time
+            )));
+        }
+        let timestamp = millis as i64;
+        if let Some(previous) = self.last_timestamp {
+            if timestamp <= previous {
+                return Err(Error::NonMonotonicTimestamp {
+                    previous,
+                    current: timestamp,
+                });
+            }
+        }
+
+        let pic = SSourcePicture {
+            uiTimeStamp: timestamp,
+            ..frame.picture()
+        };
+        let mut encoded = unsafe { self.encode_frame(&pic) }?;
+        self.last_timestamp = Some(timestamp);
+        // Frames rate control skipped report the time as well.
+        encoded.timestamp = timestamp;
+        Ok(encoded)
+    }
+
+    /// Regenerates the SPS and PPS without encoding a picture.
+    pub fn encode_parameter_sets(&mut self) -> Result<EncodedFrame> {
+        #[cfg(feature = "tracing")]
+        let span = span::encode_parameter_sets();
+
+        let mut info = SFrameBSInfo::default();
+        check({
+            #[cfg(feature = "tracing")]
+            let _entered = span.enter();
+            unsafe { (**self.raw).EncodeParameterSets.unwrap()(self.raw, &mut info) }
+        })?;
+        let frame = unsafe { EncodedFrame::from_raw(&info) };
+
+        #[cfg(feature = "tracing")]
+        span::record_encoded(&span, &frame, || None);
+        Ok(frame)
+    }
+
+    /// Regenerates the parameter sets with `EncodeParameterSets` and splits them up, e.g.
+    /// for a viewer that joins after the last IDR.
+    pub fn parameter_sets(&mut self) -> Result<ParameterSets> {
+        Ok(ParameterSets::from_frame(&self.encode_parameter_sets()?))
+    }
+
+    /// Makes sure every IDR access unit starts with the parameter sets, adding them from
+    /// `parameter_sets` when the encoder didn't write them itself. openh264 1.7 writes them
+    /// before each IDR, so this is a guarantee rather than a change for that version.
+    pub fn set_prepend_parameter_sets(&mut self, enabled: bool) {
+        self.prepend_parameter_sets = enabled;
+    }
+
+    /// Changes the target bitrate of `layer` in bits per second. Takes effect from the next
+    /// frame without an IDR.
+    pub fn set_bitrate(&mut self, layer: Layer, bitrate: i32) -> Result<()> {
+        let info = layer.to_bitrate_info(bitrate)?;
+        self.set_option(option::encoder::BITRATE, info)
+    }
+
+    /// Changes the maximum bitrate of `layer` in bits per second.
+    pub fn set_max_bitrate(&mut self, layer: Layer, bitrate: i32) -> Result<()> {
+        let info = layer.to_bitrate_info(bitrate)?;
+        self.set_option(option::encoder::MAX_BITRATE, info)
+    }
+
+    /// Changes the input frame rate. Takes effect from the next frame without an IDR.
+    pub fn set_frame_rate(&mut self, fps: f32) -> Result<()> {
+        if !fps.is_finite() || fps <= 0.0 {
+            return Err(Error::InvalidArgument(format!(
+                "Frame rate must be positive and finite, got {}",
+                fps
+            )));
+        }
+        self.set_option(option::encoder::FRAME_RATE, fps)
+    }
+
+    /// Switches the running encoder to `rate_control`, see `reconfigure`.
+    pub fn set_rate_control(&mut self, rate_control: &RateControl) -> Result<()> {
+        let mut param = self.params()?;
+        rate_control.apply(&mut param)?;
+        self.reconfigure(&param).map(|_| ())
+    }
+
+    /// Reads back the parameters the encoder is currently running with.
+    pub fn params(&self) -> Result<SEncParamExt> {
+        self.get_option(option::encoder::SVC_ENCODE_PARAM_EXT)
+    }
+
+    /// Re-initializes the running encoder with `param`.
+    ///
+    /// Returns `true` when the picture size or spatial layout changed. openh264 resets its
+    /// reference state in that case, and this method additionally forces an IDR so the next
+    /// encoded frame is always decodable on its own. Rate-only changes return `false` and
+    /// don't cost an IDR.
+    pub fn reconfigure(&mut self, param: &SEncParamExt) -> Result<bool> {
+        let current = self.params()?;
+        let layout_changed = current.iPicWidth != param.iPicWidth
+            || current.iPicHeight != param.iPicHeight
+            || current.iSpatialLayerNum != param.iSpatialLayerNum
+            || current.iTemporalLayerNum != param.iTemporalLayerNum
+            || current
+                .sSpatialLayers
+                .iter()
+                .zip(param.sSpatialLayers.iter())
+                .take(param.iSpatialLayerNum as usize)
+                .any(|(a, b)| a.iVideoWidth != b.iVideoWidth || a.iVideoHeight != b.iVideoHeight);
+        self.set_option(option::encoder::SVC_ENCODE_PARAM_EXT, *param)?;
+        if layout_changed {
+            self.force_intra_frame(true)?;
+        }
+        Ok(layout_changed)
+    }
+
+    pub fn stats(&self) -> Result<EncoderStats> {
+        self.get_option(option::encoder::GET_STATISTICS)
+            .map(EncoderStats::from)
+    }
+
+    /// Asks the encoder to recover from loss, by referencing a long-term reference frame
+    /// the decoder still has or by sending an IDR.
+    pub fn ltr_recovery_request(&mut self, request: &LtrRecoveryRequest) -> Result<()> {
+        self.set_option(option::encoder::LTR_RECOVERY_REQUEST, request.to_raw())
+    }
+
+    /// Tells the encoder whether the decoder managed to mark a long-term reference frame.
+    pub fn ltr_marking_feedback(&mut self, feedback: &LtrMarkingFeedback) -> Result<()> {
+        self.set_option(option::encoder::LTR_MARKING_FEEDBACK, feedback.to_raw())
+    }
+}
+
+impl Drop for Encoder {
+    fn drop(&mut self) {
+        unsafe {
+            (**self.raw).Uninitialize.unwrap()(self.raw);
+            WelsDestroySVCEncoder(self.raw);
+        }
+    }
+}
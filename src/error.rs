@@ -0,0 +1,49 @@
+use std::error;
+use std::fmt;
+use std::result;
+
+use DECODING_STATE;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// `WelsCreateSVCEncoder` or `WelsCreateDecoder` failed.
+    Create,
+    /// An encoder or decoder call returned a non-zero status code.
+    Native(i64),
+    /// `DecodeFrame*` returned a `DECODING_STATE` other than `dsErrorFree`.
+    Decoding(DECODING_STATE),
+    /// The caller passed a value the wrapper refused to hand to openh264.
+    InvalidArgument(String),
+    /// `Encoder::encode_at` got a timestamp, in milliseconds, that isn't past the previous one.
+    NonMonotonicTimestamp { previous: i64, current: i64 },
+    /// The worker thread of an `EncoderPool`, `AsyncEncoder` or `AsyncDecoder` has stopped.
+    Disconnected,
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Create => write!(f, "Failed to create openh264 instance"),
+            Error::Native(code) => write!(f, "openh264 call failed with status {}", code),
+            Error::Decoding(state) => write!(f, "openh264 decoding state: {:#x}", state),
+            Error::InvalidArgument(ref message) => write!(f, "Invalid argument: {}", message),
+            Error::NonMonotonicTimestamp { previous, current } => write!(
+                f,
+                "Timestamp {} ms doesn't follow the previous one at {} ms",
+                current, previous
+            ),
+            Error::Disconnected => write!(f, "Worker thread has stopped"),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+pub(crate) fn check<T: Into<i64>>(status: T) -> Result<()> {
+    match status.into() {
+        0 => Ok(()),
+        code => Err(Error::Native(code)),
+    }
+}
@@ -0,0 +1,47 @@
+//! Owned I420 pictures: encoder input for `EncoderPool` and `AsyncEncoder`, generated by
+//! `testsrc`, and what `DecodedFrame::to_frame` copies decoder output into.
+
+use error::Result;
+use yuv::YuvFrameRef;
+
+/// Owned, tightly packed I420 picture.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    /// Milliseconds, passed to openh264 as `uiTimeStamp`.
+    pub timestamp: i64,
+    /// Y, U and V planes with strides `width`, `(width + 1) / 2` and `(width + 1) / 2`.
+    pub planes: [Vec<u8>; 3],
+}
+
+impl Frame {
+    /// A black frame.
+    pub fn new(width: usize, height: usize, timestamp: i64) -> Frame {
+        let chroma = width.div_ceil(2) * height.div_ceil(2);
+        Frame {
+            width,
+            height,
+            timestamp,
+            planes: [
+                vec![0; width * height],
+                vec![128; chroma],
+                vec![128; chroma],
+            ],
+        }
+    }
+
+    /// Borrows the planes for `Encoder::encode`. Fails unless the frame is a valid I420
+    /// picture with even dimensions.
+    pub fn as_yuv(&self) -> Result<YuvFrameRef<'_>> {
+        let chroma_stride = self.width.div_ceil(2);
+        let mut frame = YuvFrameRef::new(
+            self.width,
+            self.height,
+            [&self.planes[0], &self.planes[1], &self.planes[2]],
+            [self.width, chroma_stride, chroma_stride],
+        )?;
+        frame.timestamp = self.timestamp;
+        Ok(frame)
+    }
+}
@@ -0,0 +1,226 @@
+//! Recycled owned buffers for decoder output.
+//!
+//! A `DecodedFrame` points into memory openh264 reuses on the next decode call, so every
+//! picture that is kept has to be copied. `FramePool::copy` makes that copy land in a
+//! buffer an earlier picture has given back. `PooledFrame` is a shared handle. The pool
+//! keeps one of its own to every frame it made and reuses a frame, buffer and shared
+//! allocation both, once all the others are dropped, so steady decoding of one size
+//! allocates nothing.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use decoder::DecodedFrame;
+use error::{Error, Result};
+use yuv::YuvFrameRef;
+
+struct Shared {
+    alignment: usize,
+    padding: usize,
+    /// Every frame the pool made. Those only referenced from here are free.
+    frames: Mutex<Vec<Arc<FrameData>>>,
+}
+
+/// Source of `PooledFrame`s. Clones share the same buffers.
+#[derive(Clone)]
+pub struct FramePool {
+    shared: Arc<Shared>,
+}
+
+#[derive(Default)]
+struct FrameData {
+    width: usize,
+    height: usize,
+    timestamp: u64,
+    strides: [usize; 3],
+    offsets: [usize; 3],
+    lengths: [usize; 3],
+    buffer: Vec<u8>,
+}
+
+/// Owned I420 picture in a pooled buffer. Cloning is cheap and shares the samples.
+#[derive(Clone)]
+pub struct PooledFrame {
+    data: Arc<FrameData>,
+}
+
+fn align_up(value: usize, alignment: usize) -> usize {
+    (value + alignment - 1) & !(alignment - 1)
+}
+
+impl FramePool {
+    /// Every row of every plane starts on an `alignment`-byte boundary, which must be a
+    /// power of two, and is followed by at least `padding` bytes past the picture, e.g.
+    /// for SIMD code that reads whole vectors.
+    pub fn new(alignment: usize, padding: usize) -> Result<FramePool> {
+        if !alignment.is_power_of_two() {
+            return Err(Error::InvalidArgument(format!(
+                "Alignment {} isn't a power of two",
+                alignment
+            )));
+        }
+        Ok(FramePool {
+            shared: Arc::new(Shared {
+                alignment,
+                padding,
+                frames: Mutex::new(Vec::new()),
+            }),
+        })
+    }
+
+    /// Number of frames waiting to be reused.
+    pub fn available(&self) -> usize {
+        let frames = self.shared.frames.lock().unwrap();
+        frames
+            .iter()
+            .filter(|frame| Arc::strong_count(frame) == 1)
+            .count()
+    }
+
+    /// A free frame with a buffer of at least `size` bytes if there is one, so that the
+    /// pool settles on buffers that fit; otherwise any free frame, or a new one. The frame
+    /// leaves the list, so the caller holds the only reference.
+    fn take_frame(&self, size: usize) -> Arc<FrameData> {
+        let mut frames = self.shared.frames.lock().unwrap();
+        let fitting = frames
+            .iter_mut()
+            .position(|frame| match Arc::get_mut(frame) {
+                Some(data) => data.buffer.len() >= size,
+                None => false,
+            });
+        let index = fitting.or_else(|| {
+            frames
+                .iter_mut()
+                .position(|frame| Arc::get_mut(frame).is_some())
+        });
+        match index {
+            Some(index) => frames.swap_remove(index),
+            None => Arc::new(FrameData::default()),
+        }
+    }
+
+    /// Copies `frame` out of the decoder into a pooled buffer.
+    pub fn copy(&self, frame: &DecodedFrame) -> PooledFrame {
+        let alignment = self.shared.alignment;
+        let uv_width = frame.width.div_ceil(2);
+        let uv_height = frame.height.div_ceil(2);
+        let widths = [frame.width, uv_width, uv_width];
+        let rows = [frame.height, uv_height, uv_height];
+        let sources = [
+            (frame.y, frame.y_stride),
+            (frame.u, frame.uv_stride),
+            (frame.v, frame.uv_stride),
+        ];
+
+        let mut strides = [0; 3];
+        let mut lengths = [0; 3];
+        for plane in 0..3 {
+            strides[plane] = align_up(widths[plane] + self.shared.padding, alignment);
+            lengths[plane] = strides[plane] * rows[plane];
+        }
+        // Room to move the first plane up to an aligned address; the others follow it at
+        // multiples of the alignment.
+        let size = lengths.iter().sum::<usize>() + alignment - 1;
+        let mut pooled = self.take_frame(size);
+        let data = Arc::get_mut(&mut pooled).expect("free frames are unshared");
+        let buffer = &mut data.buffer;
+        if buffer.len() < size {
+            buffer.resize(size, 0);
+        }
+        let base = (alignment - (buffer.as_ptr() as usize & (alignment - 1))) & (alignment - 1);
+        let offsets = [base, base + lengths[0], base + lengths[0] + lengths[1]];
+
+        for plane in 0..3 {
+            let (source, source_stride) = sources[plane];
+            let destination = &mut buffer[offsets[plane]..offsets[plane] + lengths[plane]];
+            let source_rows = source.chunks(source_stride).take(rows[plane]);
+            for (to, from) in destination.chunks_mut(strides[plane]).zip(source_rows) {
+                to[..widths[plane]].copy_from_slice(&from[..widths[plane]]);
+            }
+        }
+
+        data.width = frame.width;
+        data.height = frame.height;
+        data.timestamp = frame.timestamp;
+        data.strides = strides;
+        data.offsets = offsets;
+        data.lengths = lengths;
+        self.shared.frames.lock().unwrap().push(pooled.clone());
+        PooledFrame { data: pooled }
+    }
+}
+
+impl fmt::Debug for FramePool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FramePool")
+            .field("alignment", &self.shared.alignment)
+            .field("padding", &self.shared.padding)
+            .field("available", &self.available())
+            .finish()
+    }
+}
+
+impl PooledFrame {
+    pub fn width(&self) -> usize {
+        self.data.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.data.height
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.data.timestamp
+    }
+
+    /// Row strides of the Y, U and V planes, multiples of the pool's alignment.
+    pub fn strides(&self) -> [usize; 3] {
+        self.data.strides
+    }
+
+    #[cfg(test)]
+    pub(crate) fn data_ptr(&self) -> *const () {
+        &*self.data as *const FrameData as *const ()
+    }
+
+    fn plane(&self, index: usize) -> &[u8] {
+        let offset = self.data.offsets[index];
+        &self.data.buffer[offset..offset + self.data.lengths[index]]
+    }
+
+    /// Luma plane, `strides()[0]` bytes per row including padding.
+    pub fn y(&self) -> &[u8] {
+        self.plane(0)
+    }
+
+    pub fn u(&self) -> &[u8] {
+        self.plane(1)
+    }
+
+    pub fn v(&self) -> &[u8] {
+        self.plane(2)
+    }
+
+    /// Borrows the picture as encoder input, e.g. to transcode without another copy.
+    pub fn as_yuv(&self) -> Result<YuvFrameRef<'_>> {
+        let mut frame = YuvFrameRef::new(
+            self.data.width,
+            self.data.height,
+            [self.y(), self.u(), self.v()],
+            self.data.strides,
+        )?;
+        frame.timestamp = self.data.timestamp as i64;
+        Ok(frame)
+    }
+}
+
+impl fmt::Debug for PooledFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PooledFrame")
+            .field("width", &self.data.width)
+            .field("height", &self.data.height)
+            .field("timestamp", &self.data.timestamp)
+            .field("strides", &self.data.strides)
+            .finish()
+    }
+}
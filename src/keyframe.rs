@@ -0,0 +1,64 @@
+//! When an encoder starts a new IDR: periodically, on scene cuts it detects itself, and on
+//! request, e.g. when a receiver reports loss or a new viewer joins a stream.
+
+use std::os::raw::{c_int, c_uint};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use error::{Error, Result};
+use SEncParamExt;
+
+/// `uiIntraPeriod` and `bEnableSceneChangeDetect` of `SEncParamExt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyframePolicy {
+    /// Frames from one periodic IDR to the next, `0` for no periodic IDRs.
+    pub idr_interval: u32,
+    /// Let openh264 encode an IDR when the picture changes completely.
+    pub scene_change_detection: bool,
+}
+
+/// Asks an `Encoder` for an IDR from any thread. Requests are a flag that the next encode
+/// call consumes, so several requests before a frame still cost a single IDR.
+#[derive(Debug, Clone, Default)]
+pub struct KeyframeRequester {
+    requested: Arc<AtomicBool>,
+}
+
+impl KeyframePolicy {
+    /// Writes the policy into `param`, leaving unrelated fields alone.
+    pub fn apply(&self, param: &mut SEncParamExt) -> Result<()> {
+        param.uiIntraPeriod = checked_interval(self.idr_interval)? as c_uint;
+        param.bEnableSceneChangeDetect = self.scene_change_detection;
+        Ok(())
+    }
+
+    /// Reads the policy back out of `param`, e.g. from `Encoder::params()`.
+    pub fn from_params(param: &SEncParamExt) -> Self {
+        KeyframePolicy {
+            idr_interval: param.uiIntraPeriod,
+            scene_change_detection: param.bEnableSceneChangeDetect,
+        }
+    }
+}
+
+/// `ENCODER_OPTION_IDR_INTERVAL` takes a `c_int`, so intervals share its range.
+pub(crate) fn checked_interval(frames: u32) -> Result<c_int> {
+    if frames > c_int::MAX as u32 {
+        return Err(Error::InvalidArgument(format!(
+            "IDR interval {} is out of range 0..={}",
+            frames,
+            c_int::MAX
+        )));
+    }
+    Ok(frames as c_int)
+}
+
+impl KeyframeRequester {
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::Release);
+    }
+
+    pub(crate) fn take(&self) -> bool {
+        self.requested.swap(false, Ordering::AcqRel)
+    }
+}
@@ -2,7 +2,55 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+#[cfg(feature = "async")]
+extern crate futures;
+#[cfg(feature = "image")]
+extern crate image;
+#[cfg(feature = "log")]
+#[macro_use]
+extern crate log;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 
+#[cfg(feature = "async")]
+mod async_codec;
+mod decoder;
+mod encoder;
+mod error;
+mod frame;
+pub mod frame_pool;
+pub mod keyframe;
+pub mod ltr;
+pub mod metrics;
+pub mod nal;
+pub mod option;
+pub mod pool;
+pub mod preset;
+pub mod rate_control;
+#[cfg(feature = "image")]
+mod rgb;
+#[cfg(feature = "tracing")]
+mod span;
+pub mod stats;
+pub mod svc;
+pub mod testsrc;
+pub mod threading;
+pub mod timestamp;
+#[cfg(feature = "log")]
+mod trace;
+pub mod vui;
+pub mod yuv;
+
+#[cfg(feature = "async")]
+pub use async_codec::{AsyncDecoder, AsyncEncoder};
+pub use decoder::{DecodedFrame, Decoder};
+pub use encoder::{EncodedFrame, EncodedLayer, Encoder, Layer, ParameterSets};
+pub use error::{Error, Result};
+pub use frame::Frame;
+
 #[cfg(test)]
 mod test;
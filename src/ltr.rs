@@ -0,0 +1,197 @@
+//! Long-term reference (LTR) feedback messages exchanged between a decoder and the encoder
+//! that produced its stream, and a fixed-size big-endian wire form for sending them over
+//! the network.
+
+use error::{Error, Result};
+use {
+    SLTRMarkingFeedback, SLTRRecoverRequest, IDR_RECOVERY_REQUEST, LTR_MARKING_FAILED,
+    LTR_MARKING_SUCCESS, LTR_RECOVERY_REQUEST,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecoveryKind {
+    /// Predict the next frame from a long-term reference the decoder still holds.
+    Ltr,
+    /// Send an IDR frame.
+    Idr,
+}
+
+/// Safe form of `SLTRRecoverRequest`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LtrRecoveryRequest {
+    pub kind: RecoveryKind,
+    pub idr_pic_id: u32,
+    pub last_correct_frame_num: i32,
+    pub current_frame_num: i32,
+    pub layer_id: i32,
+}
+
+/// Safe form of `SLTRMarkingFeedback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LtrMarkingFeedback {
+    pub success: bool,
+    pub idr_pic_id: u32,
+    pub ltr_frame_num: i32,
+    pub layer_id: i32,
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    (u32::from(bytes[offset]) << 24)
+        | (u32::from(bytes[offset + 1]) << 16)
+        | (u32::from(bytes[offset + 2]) << 8)
+        | u32::from(bytes[offset + 3])
+}
+
+fn write_u32(bytes: &mut [u8], offset: usize, value: u32) {
+    bytes[offset] = (value >> 24) as u8;
+    bytes[offset + 1] = (value >> 16) as u8;
+    bytes[offset + 2] = (value >> 8) as u8;
+    bytes[offset + 3] = value as u8;
+}
+
+impl LtrRecoveryRequest {
+    pub const ENCODED_LEN: usize = 17;
+
+    pub fn ltr(idr_pic_id: u32, last_correct_frame_num: i32, current_frame_num: i32) -> Self {
+        LtrRecoveryRequest {
+            kind: RecoveryKind::Ltr,
+            idr_pic_id,
+            last_correct_frame_num,
+            current_frame_num,
+            layer_id: 0,
+        }
+    }
+
+    pub fn idr(idr_pic_id: u32) -> Self {
+        LtrRecoveryRequest {
+            kind: RecoveryKind::Idr,
+            idr_pic_id,
+            last_correct_frame_num: -1,
+            current_frame_num: -1,
+            layer_id: 0,
+        }
+    }
+
+    pub fn to_raw(&self) -> SLTRRecoverRequest {
+        SLTRRecoverRequest {
+            uiFeedbackType: match self.kind {
+                RecoveryKind::Ltr => LTR_RECOVERY_REQUEST as _,
+                RecoveryKind::Idr => IDR_RECOVERY_REQUEST as _,
+            },
+            uiIDRPicId: self.idr_pic_id,
+            iLastCorrectFrameNum: self.last_correct_frame_num,
+            iCurrentFrameNum: self.current_frame_num,
+            iLayerId: self.layer_id,
+        }
+    }
+
+    pub fn from_raw(raw: &SLTRRecoverRequest) -> Result<Self> {
+        let kind = match raw.uiFeedbackType as i64 {
+            t if t == LTR_RECOVERY_REQUEST as i64 => RecoveryKind::Ltr,
+            t if t == IDR_RECOVERY_REQUEST as i64 => RecoveryKind::Idr,
+            t => {
+                return Err(Error::InvalidArgument(format!(
+                    "Unknown LTR recovery request type: {}",
+                    t
+                )))
+            }
+        };
+        Ok(LtrRecoveryRequest {
+            kind,
+            idr_pic_id: raw.uiIDRPicId,
+            last_correct_frame_num: raw.iLastCorrectFrameNum,
+            current_frame_num: raw.iCurrentFrameNum,
+            layer_id: raw.iLayerId,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; LtrRecoveryRequest::ENCODED_LEN] {
+        let raw = self.to_raw();
+        let mut bytes = [0; LtrRecoveryRequest::ENCODED_LEN];
+        bytes[0] = raw.uiFeedbackType as u8;
+        write_u32(&mut bytes, 1, self.idr_pic_id);
+        write_u32(&mut bytes, 5, self.last_correct_frame_num as u32);
+        write_u32(&mut bytes, 9, self.current_frame_num as u32);
+        write_u32(&mut bytes, 13, self.layer_id as u32);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != LtrRecoveryRequest::ENCODED_LEN {
+            return Err(Error::InvalidArgument(format!(
+                "LTR recovery request must be {} bytes, got {}",
+                LtrRecoveryRequest::ENCODED_LEN,
+                bytes.len()
+            )));
+        }
+        LtrRecoveryRequest::from_raw(&SLTRRecoverRequest {
+            uiFeedbackType: u32::from(bytes[0]),
+            uiIDRPicId: read_u32(bytes, 1),
+            iLastCorrectFrameNum: read_u32(bytes, 5) as i32,
+            iCurrentFrameNum: read_u32(bytes, 9) as i32,
+            iLayerId: read_u32(bytes, 13) as i32,
+        })
+    }
+}
+
+impl LtrMarkingFeedback {
+    pub const ENCODED_LEN: usize = 13;
+
+    pub fn to_raw(&self) -> SLTRMarkingFeedback {
+        SLTRMarkingFeedback {
+            uiFeedbackType: if self.success {
+                LTR_MARKING_SUCCESS as _
+            } else {
+                LTR_MARKING_FAILED as _
+            },
+            uiIDRPicId: self.idr_pic_id,
+            iLTRFrameNum: self.ltr_frame_num,
+            iLayerId: self.layer_id,
+        }
+    }
+
+    pub fn from_raw(raw: &SLTRMarkingFeedback) -> Result<Self> {
+        let success = match raw.uiFeedbackType as i64 {
+            t if t == LTR_MARKING_SUCCESS as i64 => true,
+            t if t == LTR_MARKING_FAILED as i64 => false,
+            t => {
+                return Err(Error::InvalidArgument(format!(
+                    "Unknown LTR marking feedback type: {}",
+                    t
+                )))
+            }
+        };
+        Ok(LtrMarkingFeedback {
+            success,
+            idr_pic_id: raw.uiIDRPicId,
+            ltr_frame_num: raw.iLTRFrameNum,
+            layer_id: raw.iLayerId,
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; LtrMarkingFeedback::ENCODED_LEN] {
+        let raw = self.to_raw();
+        let mut bytes = [0; LtrMarkingFeedback::ENCODED_LEN];
+        bytes[0] = raw.uiFeedbackType as u8;
+        write_u32(&mut bytes, 1, self.idr_pic_id);
+        write_u32(&mut bytes, 5, self.ltr_frame_num as u32);
+        write_u32(&mut bytes, 9, self.layer_id as u32);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != LtrMarkingFeedback::ENCODED_LEN {
+            return Err(Error::InvalidArgument(format!(
+                "LTR marking feedback must be {} bytes, got {}",
+                LtrMarkingFeedback::ENCODED_LEN,
+                bytes.len()
+            )));
+        }
+        LtrMarkingFeedback::from_raw(&SLTRMarkingFeedback {
+            uiFeedbackType: u32::from(bytes[0]),
+            uiIDRPicId: read_u32(bytes, 1),
+            iLTRFrameNum: read_u32(bytes, 5) as i32,
+            iLayerId: read_u32(bytes, 9) as i32,
+        })
+    }
+}
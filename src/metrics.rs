@@ -0,0 +1,226 @@
+//! Per-plane PSNR and SSIM between an encoder input and the decoder output, and a
+//! round-trip harness that reports them for a whole sequence.
+//!
+//! SSIM uses 8x8 windows every 4 pixels with the usual constants for 8-bit samples,
+//! `(0.01 * 255)^2` and `(0.03 * 255)^2`. Planes smaller than a window are one window.
+
+use decoder::{DecodedFrame, Decoder};
+use encoder::Encoder;
+use error::{Error, Result};
+use frame::Frame;
+use {SDecodingParam, SEncParamExt};
+
+const SSIM_WINDOW: usize = 8;
+const SSIM_STEP: usize = 4;
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+/// One value per I420 plane.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlaneScores {
+    pub y: f64,
+    pub u: f64,
+    pub v: f64,
+}
+
+/// Quality of one decoded frame against its input. PSNR is in dB and infinite for
+/// identical planes; SSIM is 1.0 for identical planes.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameQuality {
+    pub psnr: PlaneScores,
+    pub ssim: PlaneScores,
+}
+
+/// Result of `round_trip`. Quality figures are averages over the decoded frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundTripReport {
+    pub frames: usize,
+    /// Frames the decoder output; fewer than `frames` when rate control skipped some.
+    pub decoded_frames: usize,
+    pub encoded_bytes: usize,
+    /// Bits per second, assuming the input arrives at `fMaxFrameRate`.
+    pub bitrate: f64,
+    pub psnr: PlaneScores,
+    pub ssim: PlaneScores,
+}
+
+impl PlaneScores {
+    /// Luma and chroma weighted 6:1:1, a common single-figure summary.
+    pub fn weighted(&self) -> f64 {
+        (6.0 * self.y + self.u + self.v) / 8.0
+    }
+
+    fn add(&mut self, other: &PlaneScores) {
+        self.y += other.y;
+        self.u += other.u;
+        self.v += other.v;
+    }
+
+    fn divide(&mut self, count: f64) {
+        self.y /= count;
+        self.u /= count;
+        self.v /= count;
+    }
+}
+
+struct Plane<'a> {
+    data: &'a [u8],
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+impl<'a> Plane<'a> {
+    fn row(&self, y: usize) -> &'a [u8] {
+        &self.data[y * self.stride..y * self.stride + self.width]
+    }
+}
+
+fn psnr(reference: &Plane, decoded: &Plane) -> f64 {
+    let mut squared_error = 0u64;
+    for y in 0..reference.height {
+        for (&a, &b) in reference.row(y).iter().zip(decoded.row(y)) {
+            let difference = u64::from(a.max(b) - a.min(b));
+            squared_error += difference * difference;
+        }
+    }
+    if squared_error == 0 {
+        return f64::INFINITY;
+    }
+    let mse = squared_error as f64 / (reference.width * reference.height) as f64;
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+fn window_ssim(
+    reference: &Plane,
+    decoded: &Plane,
+    left: usize,
+    top: usize,
+    size: (usize, usize),
+) -> f64 {
+    let (width, height) = size;
+    let (mut sum_a, mut sum_b, mut sum_aa, mut sum_bb, mut sum_ab) = (0u64, 0u64, 0u64, 0u64, 0u64);
+    for y in top..top + height {
+        let rows = reference.row(y)[left..left + width]
+            .iter()
+            .zip(&decoded.row(y)[left..left + width]);
+        for (&a, &b) in rows {
+            let (a, b) = (u64::from(a), u64::from(b));
+            sum_a += a;
+            sum_b += b;
+            sum_aa += a * a;
+            sum_bb += b * b;
+            sum_ab += a * b;
+        }
+    }
+    let count = (width * height) as f64;
+    let mean_a = sum_a as f64 / count;
+    let mean_b = sum_b as f64 / count;
+    let variance_a = sum_aa as f64 / count - mean_a * mean_a;
+    let variance_b = sum_bb as f64 / count - mean_b * mean_b;
+    let covariance = sum_ab as f64 / count - mean_a * mean_b;
+    ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+        / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (variance_a + variance_b + SSIM_C2))
+}
+
+fn ssim(reference: &Plane, decoded: &Plane) -> f64 {
+    let size = (
+        SSIM_WINDOW.min(reference.width),
+        SSIM_WINDOW.min(reference.height),
+    );
+    let mut total = 0.0;
+    let mut windows = 0;
+    for top in (0..=reference.height - size.1).step_by(SSIM_STEP) {
+        for left in (0..=reference.width - size.0).step_by(SSIM_STEP) {
+            total += window_ssim(reference, decoded, left, top, size);
+            windows += 1;
+        }
+    }
+    total / f64::from(windows)
+}
+
+/// Compares `decoded` against the `reference` picture it was encoded from.
+pub fn compare(reference: &Frame, decoded: &DecodedFrame) -> Result<FrameQuality> {
+    let (width, height) = (reference.width, reference.height);
+    let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+    if width == 0 || height == 0 || decoded.width != width || decoded.height != height {
+        return Err(Error::InvalidArgument(format!(
+            "Decoded size {}x{} doesn't match reference size {}x{}",
+            decoded.width, decoded.height, width, height
+        )));
+    }
+    if reference.planes[0].len() < width * height
+        || reference.planes[1].len() < chroma_width * chroma_height
+        || reference.planes[2].len() < chroma_width * chroma_height
+    {
+        return Err(Error::InvalidArgument(format!(
+            "Reference planes don't hold a {}x{} I420 picture",
+            width, height
+        )));
+    }
+    let plane = |data, stride, luma| Plane {
+        data,
+        stride,
+        width: if luma { width } else { chroma_width },
+        height: if luma { height } else { chroma_height },
+    };
+    let reference_planes = [
+        plane(&reference.planes[0], width, true),
+        plane(&reference.planes[1], chroma_width, false),
+        plane(&reference.planes[2], chroma_width, false),
+    ];
+    let decoded_planes = [
+        plane(decoded.y, decoded.y_stride, true),
+        plane(decoded.u, decoded.uv_stride, false),
+        plane(decoded.v, decoded.uv_stride, false),
+    ];
+    let score = |metric: fn(&Plane, &Plane) -> f64| PlaneScores {
+        y: metric(&reference_planes[0], &decoded_planes[0]),
+        u: metric(&reference_planes[1], &decoded_planes[1]),
+        v: metric(&reference_planes[2], &decoded_planes[2]),
+    };
+    Ok(FrameQuality {
+        psnr: score(psnr),
+        ssim: score(ssim),
+    })
+}
+
+/// Encodes `frames` with `param`, decodes the result and compares every decoded frame
+/// with its input.
+pub fn round_trip<I>(param: &SEncParamExt, frames: I) -> Result<RoundTripReport>
+where
+    I: IntoIterator<Item = Frame>,
+{
+    let mut encoder = Encoder::new()?;
+    encoder.initialize(param)?;
+    let mut decoder = Decoder::new()?;
+    decoder.initialize(&SDecodingParam::default())?;
+
+    let mut report = RoundTripReport::default();
+    for frame in frames {
+        let encoded = encoder.encode(&frame.as_yuv()?)?;
+        report.frames += 1;
+        report.encoded_bytes += encoded.size();
+        if encoded.size() == 0 {
+            continue;
+        }
+        if let Some(decoded) = decoder.decode(&encoded.to_bytes())? {
+            let quality = compare(&frame, &decoded)?;
+            report.psnr.add(&quality.psnr);
+            report.ssim.add(&quality.ssim);
+            report.decoded_frames += 1;
+        }
+    }
+    if report.decoded_frames > 0 {
+        report.psnr.divide(report.decoded_frames as f64);
+        report.ssim.divide(report.decoded_frames as f64);
+    }
+    if report.frames > 0 {
+        let seconds = report.frames as f64 / f64::from(param.fMaxFrameRate);
+        report.bitrate = report.encoded_bytes as f64 * 8.0 / seconds;
+    }
+    Ok(report)
+}
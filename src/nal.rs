@@ -0,0 +1,164 @@
+//! Annex B NAL unit parsing and a temporal sub-bitstream extractor.
+//!
+//! openh264 marks the temporal layer of every slice in the SVC extension header of NAL
+//! units 14 (prefix NAL, announcing the base layer slice that follows it) and 20 (slice
+//! of an enhancement spatial layer). Plain AVC slices without a prefix NAL carry no
+//! temporal id; `extract_temporal_layers` treats them as temporal layer 0.
+
+pub const NAL_SLICE: u8 = 1;
+pub const NAL_IDR_SLICE: u8 = 5;
+pub const NAL_SEI: u8 = 6;
+pub const NAL_SPS: u8 = 7;
+pub const NAL_PPS: u8 = 8;
+pub const NAL_PREFIX: u8 = 14;
+pub const NAL_SUBSET_SPS: u8 = 15;
+pub const NAL_SLICE_EXTENSION: u8 = 20;
+
+/// One NAL unit of an Annex B byte stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NalUnit<'a> {
+    bytes: &'a [u8],
+    header: usize,
+}
+
+/// `nal_unit_header_svc_extension()` of NAL units 14 and 20.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SvcExtension {
+    pub idr: bool,
+    pub priority_id: u8,
+    pub no_inter_layer_pred: bool,
+    pub dependency_id: u8,
+    pub quality_id: u8,
+    pub temporal_id: u8,
+    pub use_ref_base_pic: bool,
+    pub discardable: bool,
+    pub output: bool,
+}
+
+impl<'a> NalUnit<'a> {
+    /// The NAL unit including its start code.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The NAL unit without its start code, starting at the header byte.
+    pub fn payload(&self) -> &'a [u8] {
+        &self.bytes[self.header..]
+    }
+
+    pub fn nal_type(&self) -> u8 {
+        self.bytes[self.header] & 0x1f
+    }
+
+    pub fn ref_idc(&self) -> u8 {
+        (self.bytes[self.header] >> 5) & 0x3
+    }
+
+    /// The SVC extension header, for NAL units 14 and 20 that have one.
+    pub fn svc_extension(&self) -> Option<SvcExtension> {
+        match self.nal_type() {
+            NAL_PREFIX | NAL_SLICE_EXTENSION => {}
+            _ => return None,
+        }
+        let header = self.payload();
+        if header.len() < 4 || header[1] & 0x80 == 0 {
+            return None;
+        }
+        Some(SvcExtension {
+            idr: header[1] & 0x40 != 0,
+            priority_id: header[1] & 0x3f,
+            no_inter_layer_pred: header[2] & 0x80 != 0,
+            dependency_id: (header[2] >> 4) & 0x7,
+            quality_id: header[2] & 0xf,
+            temporal_id: header[3] >> 5,
+            use_ref_base_pic: header[3] & 0x10 != 0,
+            discardable: header[3] & 0x08 != 0,
+            output: header[3] & 0x04 != 0,
+        })
+    }
+}
+
+/// Iterator over the NAL units of an Annex B byte stream, see `nal_units`.
+#[derive(Debug, Clone)]
+pub struct NalUnits<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+/// Splits an Annex B byte stream at its 3- and 4-byte start codes. Bytes before the first
+/// start code are skipped.
+pub fn nal_units(data: &[u8]) -> NalUnits<'_> {
+    NalUnits { data, position: 0 }
+}
+
+/// Index of the next `00 00 01` at or after `from`.
+fn find_start_code(data: &[u8], from: usize) -> Option<usize> {
+    data[from.min(data.len())..]
+        .windows(3)
+        .position(|window| window == [0, 0, 1])
+        .map(|index| from + index)
+}
+
+impl<'a> Iterator for NalUnits<'a> {
+    type Item = NalUnit<'a>;
+
+    fn next(&mut self) -> Option<NalUnit<'a>> {
+        loop {
+            let start_code = find_start_code(self.data, self.position)?;
+            let mut start = start_code;
+            while start > self.position && self.data[start - 1] == 0 {
+                start -= 1;
+            }
+            let header = start_code + 3;
+            // Zeros before the next start code belong to it, not to this NAL unit.
+            let end = match find_start_code(self.data, header) {
+                Some(next) => {
+                    let mut end = next;
+                    while end > header && self.data[end - 1] == 0 {
+                        end -= 1;
+                    }
+                    end
+                }
+                None => self.data.len(),
+            };
+            self.position = end;
+            if header < end {
+                return Some(NalUnit {
+                    bytes: &self.data[start..end],
+                    header: header - start,
+                });
+            }
+        }
+    }
+}
+
+/// Copies the NAL units of `stream` that belong to temporal layers `0..=max_temporal_id`,
+/// along with every non-VCL NAL unit, into a new stream. A dropped prefix NAL takes the
+/// base layer slice that follows it along.
+///
+/// The result decodes as long as the dropped frames are not referenced by the kept ones,
+/// which openh264's dyadic temporal hierarchy guarantees.
+pub fn extract_temporal_layers(stream: &[u8], max_temporal_id: u8) -> Vec<u8> {
+    let mut extracted = Vec::with_capacity(stream.len());
+    let mut prefix_temporal_id = None;
+    for nal in nal_units(stream) {
+        let temporal_id = match nal.nal_type() {
+            NAL_PREFIX | NAL_SLICE_EXTENSION => nal.svc_extension().map(|svc| svc.temporal_id),
+            NAL_SLICE | NAL_IDR_SLICE => prefix_temporal_id,
+            _ => None,
+        };
+        prefix_temporal_id = if nal.nal_type() == NAL_PREFIX {
+            temporal_id
+        } else {
+            None
+        };
+        let keep = match temporal_id {
+            Some(id) => id <= max_temporal_id,
+            None => true,
+        };
+        if keep {
+            extracted.extend_from_slice(nal.bytes());
+        }
+    }
+    extracted
+}
@@ -0,0 +1,216 @@
+//! `ENCODER_OPTION` and `DECODER_OPTION` values paired with the pointee type openh264 reads
+//! or writes through the `void*` argument of `SetOption`/`GetOption`.
+//!
+//! Options whose payload is plain data can be used with `Encoder::set_option`,
+//! `Encoder::get_option`, `Encoder::get_option_with` and their `Decoder` counterparts.
+//! Options carrying pointers (trace callbacks, file paths) keep their exact type but are
+//! only reachable through the `unsafe` `set_option_unchecked` methods, since openh264
+//! holds on to those pointers.
+
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+
+use {
+    SBitrateInfo, SDecoderStatistics, SDeliveryStatus, SDumpLayer, SEncParamBase, SEncParamExt,
+    SEncoderStatistics, SLTRConfig, SLTRMarkingFeedback, SLTRRecoverRequest, SLevelInfo,
+    SProfileInfo, SVuiSarInfo, WelsTraceCallback, DECODER_OPTION, ENCODER_OPTION,
+};
+
+/// Payload types openh264 reads and writes by value, without keeping pointers around.
+///
+/// # Safety
+///
+/// Implementors must be `#[repr(C)]` plain data matching the C type openh264 expects, with
+/// no pointers inside.
+pub unsafe trait OptionValue: Copy + Default {}
+
+unsafe impl OptionValue for c_int {}
+unsafe impl OptionValue for c_uint {}
+unsafe impl OptionValue for bool {}
+unsafe impl OptionValue for f32 {}
+unsafe impl OptionValue for SBitrateInfo {}
+unsafe impl OptionValue for SDecoderStatistics {}
+unsafe impl OptionValue for SDeliveryStatus {}
+unsafe impl OptionValue for SEncParamBase {}
+unsafe impl OptionValue for SEncParamExt {}
+unsafe impl OptionValue for SEncoderStatistics {}
+unsafe impl OptionValue for SLTRConfig {}
+unsafe impl OptionValue for SLTRMarkingFeedback {}
+unsafe impl OptionValue for SLTRRecoverRequest {}
+unsafe impl OptionValue for SLevelInfo {}
+unsafe impl OptionValue for SProfileInfo {}
+unsafe impl OptionValue for SVuiSarInfo {}
+
+/// An `ENCODER_OPTION` whose payload is a `T`.
+pub struct EncoderOption<T> {
+    id: ENCODER_OPTION,
+    marker: PhantomData<T>,
+}
+
+/// A `DECODER_OPTION` whose payload is a `T`.
+pub struct DecoderOption<T> {
+    id: DECODER_OPTION,
+    marker: PhantomData<T>,
+}
+
+impl<T> EncoderOption<T> {
+    const fn new(id: ENCODER_OPTION) -> Self {
+        EncoderOption {
+            id,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> ENCODER_OPTION {
+        self.id
+    }
+}
+
+impl<T> DecoderOption<T> {
+    const fn new(id: DECODER_OPTION) -> Self {
+        DecoderOption {
+            id,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn id(&self) -> DECODER_OPTION {
+        self.id
+    }
+}
+
+impl<T> Clone for EncoderOption<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for EncoderOption<T> {}
+
+impl<T> Clone for DecoderOption<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for DecoderOption<T> {}
+
+pub mod encoder {
+    use super::*;
+    use {
+        ENCODER_LTR_MARKING_FEEDBACK, ENCODER_LTR_MARKING_PERIOD, ENCODER_LTR_RECOVERY_REQUEST,
+        ENCODER_OPTION_BITRATE, ENCODER_OPTION_BITS_VARY_PERCENTAGE, ENCODER_OPTION_COMPLEXITY,
+        ENCODER_OPTION_CURRENT_PATH, ENCODER_OPTION_DATAFORMAT, ENCODER_OPTION_DELIVERY_STATUS,
+        ENCODER_OPTION_DUMP_FILE, ENCODER_OPTION_ENABLE_PREFIX_NAL_ADDING,
+        ENCODER_OPTION_ENABLE_SSEI, ENCODER_OPTION_FRAME_RATE, ENCODER_OPTION_GET_STATISTICS,
+        ENCODER_OPTION_IDR_INTERVAL, ENCODER_OPTION_INTER_SPATIAL_PRED,
+        ENCODER_OPTION_IS_LOSSLESS_LINK, ENCODER_OPTION_LEVEL, ENCODER_OPTION_LTR,
+        ENCODER_OPTION_MAX_BITRATE, ENCODER_OPTION_NUMBER_REF, ENCODER_OPTION_PROFILE,
+        ENCODER_OPTION_RC_FRAME_SKIP, ENCODER_OPTION_RC_MODE, ENCODER_OPTION_SPS_PPS_ID_STRATEGY,
+        ENCODER_OPTION_STATISTICS_LOG_INTERVAL, ENCODER_OPTION_SVC_ENCODE_PARAM_BASE,
+        ENCODER_OPTION_SVC_ENCODE_PARAM_EXT, ENCODER_OPTION_TRACE_CALLBACK,
+        ENCODER_OPTION_TRACE_CALLBACK_CONTEXT, ENCODER_OPTION_TRACE_LEVEL, ENCODER_PADDING_PADDING,
+    };
+
+    /// `EVideoFormatType` of the input pictures.
+    pub const DATAFORMAT: EncoderOption<c_int> = EncoderOption::new(ENCODER_OPTION_DATAFORMAT);
+    pub const IDR_INTERVAL: EncoderOption<c_int> = EncoderOption::new(ENCODER_OPTION_IDR_INTERVAL);
+    pub const SVC_ENCODE_PARAM_BASE: EncoderOption<SEncParamBase> =
+        EncoderOption::new(ENCODER_OPTION_SVC_ENCODE_PARAM_BASE);
+    pub const SVC_ENCODE_PARAM_EXT: EncoderOption<SEncParamExt> =
+        EncoderOption::new(ENCODER_OPTION_SVC_ENCODE_PARAM_EXT);
+    pub const FRAME_RATE: EncoderOption<f32> = EncoderOption::new(ENCODER_OPTION_FRAME_RATE);
+    /// `iLayer` selects the layer, also when reading through `Encoder::get_option_with`.
+    pub const BITRATE: EncoderOption<SBitrateInfo> = EncoderOption::new(ENCODER_OPTION_BITRATE);
+    /// `iLayer` selects the layer, also when reading through `Encoder::get_option_with`.
+    pub const MAX_BITRATE: EncoderOption<SBitrateInfo> =
+        EncoderOption::new(ENCODER_OPTION_MAX_BITRATE);
+    pub const INTER_SPATIAL_PRED: EncoderOption<bool> =
+        EncoderOption::new(ENCODER_OPTION_INTER_SPATIAL_PRED);
+    /// `RC_MODES` value.
+    pub const RC_MODE: EncoderOption<c_int> = EncoderOption::new(ENCODER_OPTION_RC_MODE);
+    pub const RC_FRAME_SKIP: EncoderOption<bool> = EncoderOption::new(ENCODER_OPTION_RC_FRAME_SKIP);
+    pub const PADDING: EncoderOption<c_int> = EncoderOption::new(ENCODER_PADDING_PADDING);
+    pub const PROFILE: EncoderOption<SProfileInfo> = EncoderOption::new(ENCODER_OPTION_PROFILE);
+    pub const LEVEL: EncoderOption<SLevelInfo> = EncoderOption::new(ENCODER_OPTION_LEVEL);
+    pub const NUMBER_REF: EncoderOption<c_int> = EncoderOption::new(ENCODER_OPTION_NUMBER_REF);
+    pub const DELIVERY_STATUS: EncoderOption<SDeliveryStatus> =
+        EncoderOption::new(ENCODER_OPTION_DELIVERY_STATUS);
+    pub const LTR_RECOVERY_REQUEST: EncoderOption<SLTRRecoverRequest> =
+        EncoderOption::new(ENCODER_LTR_RECOVERY_REQUEST);
+    pub const LTR_MARKING_FEEDBACK: EncoderOption<SLTRMarkingFeedback> =
+        EncoderOption::new(ENCODER_LTR_MARKING_FEEDBACK);
+    pub const LTR_MARKING_PERIOD: EncoderOption<c_uint> =
+        EncoderOption::new(ENCODER_LTR_MARKING_PERIOD);
+    pub const LTR: EncoderOption<SLTRConfig> = EncoderOption::new(ENCODER_OPTION_LTR);
+    /// `ECOMPLEXITY_MODE` value.
+    pub const COMPLEXITY: EncoderOption<c_int> = EncoderOption::new(ENCODER_OPTION_COMPLEXITY);
+    pub const ENABLE_SSEI: EncoderOption<bool> = EncoderOption::new(ENCODER_OPTION_ENABLE_SSEI);
+    pub const ENABLE_PREFIX_NAL_ADDING: EncoderOption<bool> =
+        EncoderOption::new(ENCODER_OPTION_ENABLE_PREFIX_NAL_ADDING);
+    /// `EParameterSetStrategy` value.
+    pub const SPS_PPS_ID_STRATEGY: EncoderOption<c_int> =
+        EncoderOption::new(ENCODER_OPTION_SPS_PPS_ID_STRATEGY);
+    /// First byte of a NUL-terminated directory path openh264 writes its trace files to.
+    pub const CURRENT_PATH: EncoderOption<c_char> = EncoderOption::new(ENCODER_OPTION_CURRENT_PATH);
+    pub const DUMP_FILE: EncoderOption<SDumpLayer> = EncoderOption::new(ENCODER_OPTION_DUMP_FILE);
+    /// `WELS_LOG_*` value.
+    pub const TRACE_LEVEL: EncoderOption<c_int> = EncoderOption::new(ENCODER_OPTION_TRACE_LEVEL);
+    pub const TRACE_CALLBACK: EncoderOption<WelsTraceCallback> =
+        EncoderOption::new(ENCODER_OPTION_TRACE_CALLBACK);
+    pub const TRACE_CALLBACK_CONTEXT: EncoderOption<*mut c_void> =
+        EncoderOption::new(ENCODER_OPTION_TRACE_CALLBACK_CONTEXT);
+    pub const GET_STATISTICS: EncoderOption<SEncoderStatistics> =
+        EncoderOption::new(ENCODER_OPTION_GET_STATISTICS);
+    pub const STATISTICS_LOG_INTERVAL: EncoderOption<c_int> =
+        EncoderOption::new(ENCODER_OPTION_STATISTICS_LOG_INTERVAL);
+    pub const IS_LOSSLESS_LINK: EncoderOption<bool> =
+        EncoderOption::new(ENCODER_OPTION_IS_LOSSLESS_LINK);
+    pub const BITS_VARY_PERCENTAGE: EncoderOption<c_int> =
+        EncoderOption::new(ENCODER_OPTION_BITS_VARY_PERCENTAGE);
+}
+
+pub mod decoder {
+    use super::*;
+    use {
+        DECODER_OPTION_END_OF_STREAM, DECODER_OPTION_ERROR_CON_IDC, DECODER_OPTION_FRAME_NUM,
+        DECODER_OPTION_GET_SAR_INFO, DECODER_OPTION_GET_STATISTICS, DECODER_OPTION_IDR_PIC_ID,
+        DECODER_OPTION_IS_REF_PIC, DECODER_OPTION_LEVEL, DECODER_OPTION_LTR_MARKED_FRAME_NUM,
+        DECODER_OPTION_LTR_MARKING_FLAG, DECODER_OPTION_PROFILE,
+        DECODER_OPTION_STATISTICS_LOG_INTERVAL, DECODER_OPTION_TEMPORAL_ID,
+        DECODER_OPTION_TRACE_CALLBACK, DECODER_OPTION_TRACE_CALLBACK_CONTEXT,
+        DECODER_OPTION_TRACE_LEVEL, DECODER_OPTION_VCL_NAL,
+    };
+
+    /// Non-zero marks the end of the stream, flushing buffered pictures.
+    pub const END_OF_STREAM: DecoderOption<c_int> =
+        DecoderOption::new(DECODER_OPTION_END_OF_STREAM);
+    pub const VCL_NAL: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_VCL_NAL);
+    pub const TEMPORAL_ID: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_TEMPORAL_ID);
+    pub const FRAME_NUM: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_FRAME_NUM);
+    pub const IDR_PIC_ID: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_IDR_PIC_ID);
+    pub const LTR_MARKING_FLAG: DecoderOption<c_int> =
+        DecoderOption::new(DECODER_OPTION_LTR_MARKING_FLAG);
+    pub const LTR_MARKED_FRAME_NUM: DecoderOption<c_int> =
+        DecoderOption::new(DECODER_OPTION_LTR_MARKED_FRAME_NUM);
+    /// `ERROR_CON_IDC` value.
+    pub const ERROR_CON_IDC: DecoderOption<c_int> =
+        DecoderOption::new(DECODER_OPTION_ERROR_CON_IDC);
+    /// `WELS_LOG_*` value.
+    pub const TRACE_LEVEL: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_TRACE_LEVEL);
+    pub const TRACE_CALLBACK: DecoderOption<WelsTraceCallback> =
+        DecoderOption::new(DECODER_OPTION_TRACE_CALLBACK);
+    pub const TRACE_CALLBACK_CONTEXT: DecoderOption<*mut c_void> =
+        DecoderOption::new(DECODER_OPTION_TRACE_CALLBACK_CONTEXT);
+    pub const GET_STATISTICS: DecoderOption<SDecoderStatistics> =
+        DecoderOption::new(DECODER_OPTION_GET_STATISTICS);
+    pub const GET_SAR_INFO: DecoderOption<SVuiSarInfo> =
+        DecoderOption::new(DECODER_OPTION_GET_SAR_INFO);
+    /// `EProfileIdc` value.
+    pub const PROFILE: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_PROFILE);
+    /// `ELevelIdc` value.
+    pub const LEVEL: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_LEVEL);
+    pub const STATISTICS_LOG_INTERVAL: DecoderOption<c_int> =
+        DecoderOption::new(DECODER_OPTION_STATISTICS_LOG_INTERVAL);
+    pub const IS_REF_PIC: DecoderOption<c_int> = DecoderOption::new(DECODER_OPTION_IS_REF_PIC);
+}
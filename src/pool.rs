@@ -0,0 +1,118 @@
+//! Runs one `Encoder` per stream on a fixed set of worker threads.
+//!
+//! Stream `i` is owned by worker `i % threads`, which encodes its frames in submission
+//! order, so results of one stream come out in the order they went in. Results of
+//! different streams interleave.
+//!
+//! Both the per-worker input queues and the result channel are bounded: `submit` blocks
+//! while the stream's worker is `queue_depth` frames behind, and workers block while
+//! results aren't received. Receive results on a different thread than the one that
+//! submits, or interleave the two, to avoid a deadlock.
+//!
+//! Dropping the pool doesn't wait for the workers. They encode what is still queued and
+//! exit, and the result channel disconnects after the last result.
+
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread;
+
+use encoder::{EncodedFrame, Encoder};
+use error::{Error, Result};
+use frame::Frame;
+use SEncParamExt;
+
+/// One encoded frame, or the error encoding it failed with.
+#[derive(Debug)]
+pub struct Output {
+    pub stream: usize,
+    pub frame: Result<EncodedFrame>,
+}
+
+struct Job {
+    stream: usize,
+    frame: Frame,
+}
+
+/// A set of encoders spread over worker threads, see the module documentation.
+pub struct EncoderPool {
+    queues: Vec<SyncSender<Job>>,
+    streams: usize,
+}
+
+impl EncoderPool {
+    /// Creates and initializes one encoder per entry of `params` and hands them to
+    /// `threads` workers. Results arrive on the returned receiver, which closes once the
+    /// pool is dropped and every queued frame has been encoded, so it can be drained after
+    /// the drop.
+    pub fn new(
+        params: &[SEncParamExt],
+        threads: usize,
+        queue_depth: usize,
+    ) -> Result<(EncoderPool, Receiver<Output>)> {
+        if threads == 0 || queue_depth == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "Pool needs at least one thread and queue slot, got {} and {}",
+                threads, queue_depth
+            )));
+        }
+        let mut assigned: Vec<Vec<(usize, Encoder)>> = (0..threads).map(|_| Vec::new()).collect();
+        for (stream, param) in params.iter().enumerate() {
+            let mut encoder = Encoder::new()?;
+            encoder.initialize(param)?;
+            assigned[stream % threads].push((stream, encoder));
+        }
+
+        let (results, receiver) = sync_channel(threads * queue_depth);
+        let mut queues = Vec::with_capacity(threads);
+        for encoders in assigned {
+            let (queue, jobs) = sync_channel(queue_depth);
+            let results = results.clone();
+            queues.push(queue);
+            thread::spawn(move || run_worker(encoders, jobs, results));
+        }
+        let pool = EncoderPool {
+            queues,
+            streams: params.len(),
+        };
+        Ok((pool, receiver))
+    }
+
+    pub fn streams(&self) -> usize {
+        self.streams
+    }
+
+    /// Queues `frame` for encoding on `stream`, blocking while that stream's worker is
+    /// busy with `queue_depth` earlier frames.
+    pub fn submit(&self, stream: usize, frame: Frame) -> Result<()> {
+        if stream >= self.streams {
+            return Err(Error::InvalidArgument(format!(
+                "Stream {} is out of range 0..{}",
+                stream, self.streams
+            )));
+        }
+        self.queues[stream % self.queues.len()]
+            .send(Job { stream, frame })
+            .map_err(|_| Error::Disconnected)
+    }
+}
+
+fn run_worker(
+    mut encoders: Vec<(usize, Encoder)>,
+    jobs: Receiver<Job>,
+    results: SyncSender<Output>,
+) {
+    for job in jobs {
+        let encoder = match encoders.iter_mut().find(|entry| entry.0 == job.stream) {
+            Some(entry) => &mut entry.1,
+            None => continue,
+        };
+        let frame = job.frame.as_yuv().and_then(|frame| encoder.encode(&frame));
+        let output = Output {
+            stream: job.stream,
+            frame,
+        };
+        if results.send(output).is_err() {
+            // Nobody is listening any more.
+            return;
+        }
+    }
+}
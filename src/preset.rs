@@ -0,0 +1,145 @@
+//! Named encoder configurations for common uses, built from the typed pieces in `svc`,
+//! `rate_control`, `keyframe` and `threading` plus the content-analysis switches of
+//! `SEncParamExt`. Every preset encodes one spatial layer; adjust the returned parameters
+//! for anything more specific.
+
+use encoder::Encoder;
+use error::{Error, Result};
+use keyframe::KeyframePolicy;
+use rate_control::{FrameSkip, RateControl, MAX_QP};
+use svc::{SpatialLayer, SvcLayout};
+use threading::EncoderThreads;
+use {
+    SEncParamExt, CAMERA_VIDEO_NON_REAL_TIME, CAMERA_VIDEO_REAL_TIME, HIGH_COMPLEXITY,
+    LOW_COMPLEXITY, MEDIUM_COMPLEXITY, PRO_BASELINE, PRO_MAIN, SCREEN_CONTENT_REAL_TIME,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// Desktop or window capture. Screen-content tools and background detection, which
+    /// skips unchanged areas, without adaptive quantization, which blurs text. Rate control
+    /// paces by timestamp, since capture only delivers frames when something changes.
+    /// IDRs only on request.
+    ScreenShare,
+    /// Camera conferencing. Denoising, adaptive quantization and frame skipping to hold
+    /// the bitrate, and two temporal layers so a forwarding server can halve the frame
+    /// rate. IDRs on scene changes and on request.
+    VideoCall,
+    /// Offline encoding for storage. Main profile with CABAC, the slowest motion search,
+    /// quality-first rate control, all cores, and an IDR every two seconds for seeking.
+    Archive,
+    /// Game streaming at high frame rates. Fast motion search, no frame skipping, the peak
+    /// bitrate capped at the target, and one slice per thread so slices can go out as
+    /// they finish. No scene-change IDRs, whose size spikes would show up as latency.
+    LowLatencyGame,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::ScreenShare,
+        Preset::VideoCall,
+        Preset::Archive,
+        Preset::LowLatencyGame,
+    ];
+
+    /// The preset's parameters for a `width` x `height` stream at up to `frame_rate` frames
+    /// per second and `bitrate` bits per second, on top of `encoder`'s defaults.
+    pub fn params(
+        &self,
+        encoder: &Encoder,
+        width: u32,
+        height: u32,
+        frame_rate: f32,
+        bitrate: u32,
+    ) -> Result<SEncParamExt> {
+        if frame_rate.is_nan() || frame_rate <= 0.0 {
+            return Err(Error::InvalidArgument(format!(
+                "Frame rate must be positive, got {}",
+                frame_rate
+            )));
+        }
+        let mut param = encoder.default_params()?;
+        let mut layer = SpatialLayer::new(width, height, frame_rate, bitrate);
+        let mut temporal_layers = 1;
+        let mut keyframes = KeyframePolicy {
+            idr_interval: 0,
+            scene_change_detection: true,
+        };
+        let mut threads = EncoderThreads::new(1);
+        let rate_control;
+
+        match *self {
+            Preset::ScreenShare => {
+                param.iUsageType = SCREEN_CONTENT_REAL_TIME;
+                param.iComplexityMode = MEDIUM_COMPLEXITY;
+                param.bEnableDenoise = false;
+                param.bEnableBackgroundDetection = true;
+                param.bEnableAdaptiveQuant = false;
+                layer.profile = PRO_BASELINE;
+                rate_control = RateControl::Timestamp {
+                    bitrate,
+                    max_bitrate: None,
+                    frame_skip: true,
+                };
+            }
+            Preset::VideoCall => {
+                param.iUsageType = CAMERA_VIDEO_REAL_TIME;
+                param.iComplexityMode = MEDIUM_COMPLEXITY;
+                param.bEnableDenoise = true;
+                param.bEnableBackgroundDetection = true;
+                param.bEnableAdaptiveQuant = true;
+                layer.profile = PRO_BASELINE;
+                temporal_layers = 2;
+                rate_control = RateControl::Bitrate {
+                    bitrate,
+                    max_bitrate: None,
+                    frame_skip: FrameSkip::BeforeEncoding,
+                };
+            }
+            Preset::Archive => {
+                param.iUsageType = CAMERA_VIDEO_NON_REAL_TIME;
+                param.iComplexityMode = HIGH_COMPLEXITY;
+                param.iEntropyCodingModeFlag = 1;
+                param.bEnableDenoise = false;
+                param.bEnableBackgroundDetection = true;
+                param.bEnableAdaptiveQuant = true;
+                layer.profile = PRO_MAIN;
+                keyframes.idr_interval = (frame_rate * 2.0).round() as u32;
+                threads = EncoderThreads::new(0);
+                rate_control = RateControl::Quality {
+                    bitrate,
+                    min_qp: 0,
+                    max_qp: MAX_QP,
+                };
+            }
+            Preset::LowLatencyGame => {
+                param.iUsageType = CAMERA_VIDEO_REAL_TIME;
+                param.iComplexityMode = LOW_COMPLEXITY;
+                param.iNumRefFrame = 1;
+                param.bEnableDenoise = false;
+                param.bEnableBackgroundDetection = false;
+                param.bEnableAdaptiveQuant = false;
+                layer.profile = PRO_BASELINE;
+                keyframes.scene_change_detection = false;
+                threads = EncoderThreads::new(4);
+                rate_control = RateControl::Bitrate {
+                    bitrate,
+                    max_bitrate: Some(bitrate),
+                    frame_skip: FrameSkip::Never,
+                };
+            }
+        }
+        param.bPrefixNalAddingCtrl = false;
+
+        SvcLayout {
+            spatial_layers: vec![layer],
+            temporal_layers,
+            simulcast: false,
+        }
+        .apply(&mut param)?;
+        rate_control.apply(&mut param)?;
+        keyframes.apply(&mut param)?;
+        threads.apply(&mut param)?;
+        Ok(param)
+    }
+}
@@ -0,0 +1,218 @@
+//! Typed rate control configuration covering every `RC_MODES` value.
+//!
+//! Each mode reads a different subset of `SEncParamExt`: the bitrate modes need
+//! `iTargetBitrate` and honor `iMaxBitrate` and `bEnableFrameSkip`, the buffer-based mode
+//! only moves the QP within `iMinQp..=iMaxQp`, and `RC_OFF_MODE` encodes at the QP of each
+//! spatial layer. `RateControl::apply` writes all of those fields so that nothing left
+//! over from an earlier mode takes effect.
+
+use std::os::raw::c_int;
+
+use error::{Error, Result};
+use {
+    SEncParamExt, RC_BITRATE_MODE, RC_BITRATE_MODE_POST_SKIP, RC_BUFFERBASED_MODE, RC_MODES,
+    RC_OFF_MODE, RC_QUALITY_MODE, RC_TIMESTAMP_MODE, UNSPECIFIED_BIT_RATE,
+};
+
+/// Highest QP H.264 allows.
+pub const MAX_QP: u8 = 51;
+
+/// Whether and when a bitrate mode may drop frames to stay within budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSkip {
+    Never,
+    /// Decide before encoding a frame, `RC_BITRATE_MODE` with `bEnableFrameSkip`.
+    BeforeEncoding,
+    /// Encode every frame and drop it afterwards if it overshot,
+    /// `RC_BITRATE_MODE_POST_SKIP`.
+    AfterEncoding,
+}
+
+/// Rate control mode together with the parameters it reads. Bitrates are in bits per
+/// second; a `max_bitrate` of `None` leaves the peak unconstrained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateControl {
+    /// `RC_QUALITY_MODE`: aims for `bitrate` but favors picture quality within the QP range.
+    Quality {
+        bitrate: u32,
+        min_qp: u8,
+        max_qp: u8,
+    },
+    /// `RC_BITRATE_MODE` or `RC_BITRATE_MODE_POST_SKIP`: holds `bitrate`.
+    Bitrate {
+        bitrate: u32,
+        max_bitrate: Option<u32>,
+        frame_skip: FrameSkip,
+    },
+    /// `RC_BUFFERBASED_MODE`: no bitrate target, the QP follows the fullness of the
+    /// encoder's buffer. Meant for screen content.
+    Buffer { min_qp: u8, max_qp: u8 },
+    /// `RC_TIMESTAMP_MODE`: holds `bitrate` over the input timestamps instead of the
+    /// configured frame rate, for sources with an irregular frame rate.
+    Timestamp {
+        bitrate: u32,
+        max_bitrate: Option<u32>,
+        frame_skip: bool,
+    },
+    /// `RC_OFF_MODE`: every frame at `qp`.
+    ConstantQp { qp: u8 },
+}
+
+fn checked_bitrate(bitrate: u32) -> Result<c_int> {
+    if bitrate == 0 || bitrate > c_int::MAX as u32 {
+        return Err(Error::InvalidArgument(format!(
+            "Bitrate {} is out of range 1..={}",
+            bitrate,
+            c_int::MAX
+        )));
+    }
+    Ok(bitrate as c_int)
+}
+
+fn checked_max_bitrate(bitrate: u32, max_bitrate: Option<u32>) -> Result<c_int> {
+    match max_bitrate {
+        None => Ok(UNSPECIFIED_BIT_RATE as c_int),
+        Some(max) if max < bitrate => Err(Error::InvalidArgument(format!(
+            "Maximum bitrate {} is below the target bitrate {}",
+            max, bitrate
+        ))),
+        Some(max) => checked_bitrate(max),
+    }
+}
+
+fn checked_qp_range(min_qp: u8, max_qp: u8) -> Result<(c_int, c_int)> {
+    if min_qp > max_qp || max_qp > MAX_QP {
+        return Err(Error::InvalidArgument(format!(
+            "QP range {}..={} isn't within 0..={}",
+            min_qp, max_qp, MAX_QP
+        )));
+    }
+    Ok((c_int::from(min_qp), c_int::from(max_qp)))
+}
+
+impl RateControl {
+    fn mode(&self) -> RC_MODES {
+        match *self {
+            RateControl::Quality { .. } => RC_QUALITY_MODE,
+            RateControl::Bitrate {
+                frame_skip: FrameSkip::AfterEncoding,
+                ..
+            } => RC_BITRATE_MODE_POST_SKIP,
+            RateControl::Bitrate { .. } => RC_BITRATE_MODE,
+            RateControl::Buffer { .. } => RC_BUFFERBASED_MODE,
+            RateControl::Timestamp { .. } => RC_TIMESTAMP_MODE,
+            RateControl::ConstantQp { .. } => RC_OFF_MODE,
+        }
+    }
+
+    /// Validates the parameters and writes them into `param`, leaving unrelated fields
+    /// alone. With one spatial layer its bitrates are set too; with more, the split across
+    /// layers is up to the caller, e.g. through `svc::SvcLayout`.
+    pub fn apply(&self, param: &mut SEncParamExt) -> Result<()> {
+        let unset = UNSPECIFIED_BIT_RATE as c_int;
+        let (bitrate, max_bitrate, (min_qp, max_qp), frame_skip, layer_qp) = match *self {
+            RateControl::Quality {
+                bitrate,
+                min_qp,
+                max_qp,
+            } => (
+                checked_bitrate(bitrate)?,
+                unset,
+                checked_qp_range(min_qp, max_qp)?,
+                false,
+                None,
+            ),
+            RateControl::Bitrate {
+                bitrate,
+                max_bitrate,
+                frame_skip,
+            } => (
+                checked_bitrate(bitrate)?,
+                checked_max_bitrate(bitrate, max_bitrate)?,
+                (0, c_int::from(MAX_QP)),
+                frame_skip != FrameSkip::Never,
+                None,
+            ),
+            RateControl::Buffer { min_qp, max_qp } => (
+                param.iTargetBitrate,
+                unset,
+                checked_qp_range(min_qp, max_qp)?,
+                false,
+                None,
+            ),
+            RateControl::Timestamp {
+                bitrate,
+                max_bitrate,
+                frame_skip,
+            } => (
+                checked_bitrate(bitrate)?,
+                checked_max_bitrate(bitrate, max_bitrate)?,
+                (0, c_int::from(MAX_QP)),
+                frame_skip,
+                None,
+            ),
+            RateControl::ConstantQp { qp } => {
+                let (qp, _) = checked_qp_range(qp, qp)?;
+                (param.iTargetBitrate, unset, (qp, qp), false, Some(qp))
+            }
+        };
+
+        param.iRCMode = self.mode();
+        param.iTargetBitrate = bitrate;
+        param.iMaxBitrate = max_bitrate;
+        param.iMinQp = min_qp;
+        param.iMaxQp = max_qp;
+        param.bEnableFrameSkip = frame_skip;
+        if param.iSpatialLayerNum == 1 {
+            param.sSpatialLayers[0].iSpatialBitrate = bitrate;
+            param.sSpatialLayers[0].iMaxSpatialBitrate = max_bitrate;
+        }
+        if let Some(qp) = layer_qp {
+            for layer in param.sSpatialLayers.iter_mut() {
+                layer.iDLayerQp = qp;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the rate control back out of `param`, e.g. from `Encoder::params()`.
+    pub fn from_params(param: &SEncParamExt) -> Result<Self> {
+        let bitrate = param.iTargetBitrate.max(0) as u32;
+        let max_bitrate = match param.iMaxBitrate {
+            max if max <= UNSPECIFIED_BIT_RATE as c_int => None,
+            max => Some(max as u32),
+        };
+        let (min_qp, max_qp) = (param.iMinQp.max(0) as u8, param.iMaxQp.max(0) as u8);
+        Ok(match param.iRCMode {
+            RC_QUALITY_MODE => RateControl::Quality {
+                bitrate,
+                min_qp,
+                max_qp,
+            },
+            RC_BITRATE_MODE | RC_BITRATE_MODE_POST_SKIP => RateControl::Bitrate {
+                bitrate,
+                max_bitrate,
+                frame_skip: match (param.iRCMode, param.bEnableFrameSkip) {
+                    (RC_BITRATE_MODE_POST_SKIP, _) => FrameSkip::AfterEncoding,
+                    (_, true) => FrameSkip::BeforeEncoding,
+                    (_, false) => FrameSkip::Never,
+                },
+            },
+            RC_BUFFERBASED_MODE => RateControl::Buffer { min_qp, max_qp },
+            RC_TIMESTAMP_MODE => RateControl::Timestamp {
+                bitrate,
+                max_bitrate,
+                frame_skip: param.bEnableFrameSkip,
+            },
+            RC_OFF_MODE => RateControl::ConstantQp {
+                qp: param.sSpatialLayers[0].iDLayerQp.max(0) as u8,
+            },
+            mode => {
+                return Err(Error::InvalidArgument(format!(
+                    "Unknown rate control mode {}",
+                    mode
+                )))
+            }
+        })
+    }
+}
@@ -0,0 +1,122 @@
+//! Conversions between I420 pictures and `image::RgbImage`, using the BT.601
+//! limited-range matrix openh264 assumes when a stream signals no color description.
+
+use image::{DynamicImage, RgbImage};
+
+use decoder::DecodedFrame;
+use frame::Frame;
+use frame_pool::PooledFrame;
+
+fn clamp(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn pixel(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = 298 * (i32::from(y) - 16);
+    let d = i32::from(u) - 128;
+    let e = i32::from(v) - 128;
+    [
+        clamp((c + 409 * e + 128) >> 8),
+        clamp((c - 100 * d - 208 * e + 128) >> 8),
+        clamp((c + 516 * d + 128) >> 8),
+    ]
+}
+
+fn to_rgb(width: usize, height: usize, planes: [&[u8]; 3], strides: [usize; 3]) -> RgbImage {
+    let mut data = Vec::with_capacity(width * height * 3);
+    for row in 0..height {
+        let luma = &planes[0][row * strides[0]..][..width];
+        let u = &planes[1][row / 2 * strides[1]..];
+        let v = &planes[2][row / 2 * strides[2]..];
+        for (x, &y) in luma.iter().enumerate() {
+            data.extend_from_slice(&pixel(y, u[x / 2], v[x / 2]));
+        }
+    }
+    RgbImage::from_raw(width as u32, height as u32, data).expect("Buffer fits the image size")
+}
+
+impl<'a> DecodedFrame<'a> {
+    pub fn to_rgb_image(&self) -> RgbImage {
+        to_rgb(
+            self.width,
+            self.height,
+            [self.y, self.u, self.v],
+            [self.y_stride, self.uv_stride, self.uv_stride],
+        )
+    }
+}
+
+impl PooledFrame {
+    pub fn to_rgb_image(&self) -> RgbImage {
+        to_rgb(
+            self.width(),
+            self.height(),
+            [self.y(), self.u(), self.v()],
+            self.strides(),
+        )
+    }
+}
+
+impl Frame {
+    pub fn to_rgb_image(&self) -> RgbImage {
+        let chroma_stride = self.width.div_ceil(2);
+        to_rgb(
+            self.width,
+            self.height,
+            [&self.planes[0], &self.planes[1], &self.planes[2]],
+            [self.width, chroma_stride, chroma_stride],
+        )
+    }
+
+    /// Converts `image` to encoder input. Chroma is the average of each 2x2 block.
+    pub fn from_rgb_image(image: &RgbImage, timestamp: i64) -> Frame {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let chroma_width = width.div_ceil(2);
+        let pixels = image.as_raw();
+        let rgb = |x: usize, y: usize| {
+            let offset = (y * width + x) * 3;
+            [
+                i32::from(pixels[offset]),
+                i32::from(pixels[offset + 1]),
+                i32::from(pixels[offset + 2]),
+            ]
+        };
+
+        let mut frame = Frame::new(width, height, timestamp);
+        for y in 0..height {
+            for x in 0..width {
+                let [r, g, b] = rgb(x, y);
+                frame.planes[0][y * width + x] =
+                    (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8;
+            }
+        }
+        for chroma_y in 0..height.div_ceil(2) {
+            for chroma_x in 0..chroma_width {
+                let mut sum = [0; 3];
+                let mut count = 0;
+                for y in chroma_y * 2..(chroma_y * 2 + 2).min(height) {
+                    for x in chroma_x * 2..(chroma_x * 2 + 2).min(width) {
+                        for (sum, value) in sum.iter_mut().zip(&rgb(x, y)) {
+                            *sum += value;
+                        }
+                        count += 1;
+                    }
+                }
+                let [r, g, b] = [
+                    (sum[0] + count / 2) / count,
+                    (sum[1] + count / 2) / count,
+                    (sum[2] + count / 2) / count,
+                ];
+                let offset = chroma_y * chroma_width + chroma_x;
+                frame.planes[1][offset] = (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8;
+                frame.planes[2][offset] = (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8;
+            }
+        }
+        frame
+    }
+
+    /// `from_rgb_image` for any pixel format `image` can convert to RGB.
+    pub fn from_image(image: &DynamicImage, timestamp: i64) -> Frame {
+        Frame::from_rgb_image(&image.to_rgb8(), timestamp)
+    }
+}
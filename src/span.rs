@@ -0,0 +1,107 @@
+//! `tracing` spans around the openh264 calls `Encoder` and `Decoder` make. Each span is
+//! entered for exactly one native call, so subscribers that record the time spent in a span
+//! get per-frame encoder and decoder latency. Keyframe requests, parameter-set handling and
+//! the statistics query for `average_qp` happen outside it; the fields are recorded after
+//! the span is left.
+//!
+//! `average_qp` comes from the encoder or decoder statistics, which is the finest QP
+//! openh264 reports. It is only queried when the span is enabled.
+
+use std::os::raw::c_int;
+
+use tracing::field::Empty;
+use tracing::{debug_span, Span};
+
+use encoder::EncodedFrame;
+use {
+    videoFrameTypeI, videoFrameTypeIDR, videoFrameTypeIPMixed, videoFrameTypeP, videoFrameTypeSkip,
+    EVideoFrameType, DECODING_STATE,
+};
+
+fn frame_type_name(frame_type: EVideoFrameType) -> &'static str {
+    match frame_type {
+        videoFrameTypeIDR => "IDR",
+        videoFrameTypeI => "I",
+        videoFrameTypeP => "P",
+        videoFrameTypeSkip => "Skip",
+        videoFrameTypeIPMixed => "IPMixed",
+        _ => "Invalid",
+    }
+}
+
+pub(crate) fn encode_frame(width: c_int, height: c_int) -> Span {
+    debug_span!(
+        "EncodeFrame",
+        width,
+        height,
+        frame_type = Empty,
+        bytes = Empty,
+        layers = Empty,
+        average_qp = Empty
+    )
+}
+
+pub(crate) fn encode_parameter_sets() -> Span {
+    debug_span!("EncodeParameterSets", bytes = Empty, layers = Empty)
+}
+
+pub(crate) fn decode_frame_no_delay(bytes: usize) -> Span {
+    debug_span!(
+        "DecodeFrameNoDelay",
+        bytes,
+        state = Empty,
+        frame_ready = Empty,
+        width = Empty,
+        height = Empty,
+        average_qp = Empty
+    )
+}
+
+pub(crate) fn decode_frame2(bytes: usize) -> Span {
+    debug_span!(
+        "DecodeFrame2",
+        bytes,
+        state = Empty,
+        frame_ready = Empty,
+        width = Empty,
+        height = Empty,
+        average_qp = Empty
+    )
+}
+
+pub(crate) fn record_encoded<F>(span: &Span, frame: &EncodedFrame, average_qp: F)
+where
+    F: FnOnce() -> Option<u32>,
+{
+    if span.is_disabled() {
+        return;
+    }
+    span.record("frame_type", frame_type_name(frame.frame_type));
+    span.record("bytes", frame.size() as u64);
+    span.record("layers", frame.layers.len() as u64);
+    if let Some(qp) = average_qp() {
+        span.record("average_qp", qp);
+    }
+}
+
+pub(crate) fn record_decoded<F>(
+    span: &Span,
+    state: DECODING_STATE,
+    frame_size: Option<(usize, usize)>,
+    average_qp: F,
+) where
+    F: FnOnce() -> Option<i32>,
+{
+    if span.is_disabled() {
+        return;
+    }
+    span.record("state", state as u64);
+    span.record("frame_ready", frame_size.is_some());
+    if let Some((width, height)) = frame_size {
+        span.record("width", width as u64);
+        span.record("height", height as u64);
+    }
+    if let Some(qp) = average_qp() {
+        span.record("average_qp", qp);
+    }
+}
@@ -0,0 +1,296 @@
+//! Plain Rust copies of `SEncoderStatistics`/`SDecoderStatistics` and a sampler that turns
+//! their running totals into per-interval deltas for monitoring.
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use {SDecoderStatistics, SEncoderStatistics};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderStats {
+    pub width: u32,
+    pub height: u32,
+    pub average_frame_speed_ms: f32,
+    pub average_frame_rate: f32,
+    pub latest_frame_rate: f32,
+    pub bitrate: u32,
+    pub average_frame_qp: u32,
+    pub input_frame_count: u32,
+    pub skipped_frame_count: u32,
+    pub resolution_change_count: u32,
+    pub idr_request_count: u32,
+    pub idr_sent_count: u32,
+    pub ltr_sent_count: u32,
+    pub total_encoded_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecoderStats {
+    pub width: u32,
+    pub height: u32,
+    pub average_frame_speed_ms: f32,
+    pub actual_average_frame_speed_ms: f32,
+    pub decoded_frame_count: u32,
+    pub resolution_change_count: u32,
+    pub idr_correct_count: u32,
+    pub average_concealment_ratio: u32,
+    pub average_concealment_propagation_ratio: u32,
+    pub concealed_idr_count: u32,
+    pub concealed_frame_count: u32,
+    pub idr_lost_count: u32,
+    pub freezing_idr_count: u32,
+    pub freezing_non_idr_count: u32,
+    pub average_luma_qp: i32,
+    pub sps_error_count: i32,
+    pub subset_sps_error_count: i32,
+    pub pps_error_count: i32,
+    pub missing_sps_count: i32,
+    pub missing_subset_sps_count: i32,
+    pub missing_pps_count: i32,
+    pub profile: u32,
+    pub level: u32,
+}
+
+impl From<SEncoderStatistics> for EncoderStats {
+    // `unsigned long` is only 32 bits wide on Windows.
+    #[allow(clippy::useless_conversion)]
+    fn from(raw: SEncoderStatistics) -> Self {
+        EncoderStats {
+            width: raw.uiWidth,
+            height: raw.uiHeight,
+            average_frame_speed_ms: raw.fAverageFrameSpeedInMs,
+            average_frame_rate: raw.fAverageFrameRate,
+            latest_frame_rate: raw.fLatestFrameRate,
+            bitrate: raw.uiBitRate,
+            average_frame_qp: raw.uiAverageFrameQP,
+            input_frame_count: raw.uiInputFrameCount,
+            skipped_frame_count: raw.uiSkippedFrameCount,
+            resolution_change_count: raw.uiResolutionChangeTimes,
+            idr_request_count: raw.uiIDRReqNum,
+            idr_sent_count: raw.uiIDRSentNum,
+            ltr_sent_count: raw.uiLTRSentNum,
+            total_encoded_bytes: u64::from(raw.iTotalEncodedBytes),
+        }
+    }
+}
+
+impl From<SDecoderStatistics> for DecoderStats {
+    fn from(raw: SDecoderStatistics) -> Self {
+        DecoderStats {
+            width: raw.uiWidth,
+            height: raw.uiHeight,
+            average_frame_speed_ms: raw.fAverageFrameSpeedInMs,
+            actual_average_frame_speed_ms: raw.fActualAverageFrameSpeedInMs,
+            decoded_frame_count: raw.uiDecodedFrameCount,
+            resolution_change_count: raw.uiResolutionChangeTimes,
+            idr_correct_count: raw.uiIDRCorrectNum,
+            average_concealment_ratio: raw.uiAvgEcRatio,
+            average_concealment_propagation_ratio: raw.uiAvgEcPropRatio,
+            concealed_idr_count: raw.uiEcIDRNum,
+            concealed_frame_count: raw.uiEcFrameNum,
+            idr_lost_count: raw.uiIDRLostNum,
+            freezing_idr_count: raw.uiFreezingIDRNum,
+            freezing_non_idr_count: raw.uiFreezingNonIDRNum,
+            average_luma_qp: raw.iAvgLumaQp,
+            sps_error_count: raw.iSpsReportErrorNum,
+            subset_sps_error_count: raw.iSubSpsReportErrorNum,
+            pps_error_count: raw.iPpsReportErrorNum,
+            missing_sps_count: raw.iSpsNoExistNalNum,
+            missing_subset_sps_count: raw.iSubSpsNoExistNalNum,
+            missing_pps_count: raw.iPpsNoExistNalNum,
+            profile: raw.uiProfile,
+            level: raw.uiLevel,
+        }
+    }
+}
+
+impl fmt::Display for EncoderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} {:.1}fps {}bps qp={} frames={} skipped={} idr={}/{} ltr={} bytes={}",
+            self.width,
+            self.height,
+            self.average_frame_rate,
+            self.bitrate,
+            self.average_frame_qp,
+            self.input_frame_count,
+            self.skipped_frame_count,
+            self.idr_sent_count,
+            self.idr_request_count,
+            self.ltr_sent_count,
+            self.total_encoded_bytes
+        )
+    }
+}
+
+impl fmt::Display for DecoderStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} {:.2}ms/frame qp={} frames={} concealed={} idr_lost={}",
+            self.width,
+            self.height,
+            self.average_frame_speed_ms,
+            self.average_luma_qp,
+            self.decoded_frame_count,
+            self.concealed_frame_count,
+            self.idr_lost_count
+        )
+    }
+}
+
+/// Change of `EncoderStats` counters over one sampling interval.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncoderStatsDelta {
+    pub elapsed: Duration,
+    pub input_frames: u32,
+    pub skipped_frames: u32,
+    pub idr_sent: u32,
+    pub ltr_sent: u32,
+    pub encoded_bytes: u64,
+}
+
+/// Change of `DecoderStats` counters over one sampling interval.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecoderStatsDelta {
+    pub elapsed: Duration,
+    pub decoded_frames: u32,
+    pub concealed_frames: u32,
+    pub idr_lost: u32,
+}
+
+fn per_second(count: f64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        count / seconds
+    } else {
+        0.0
+    }
+}
+
+impl EncoderStatsDelta {
+    pub fn frame_rate(&self) -> f64 {
+        let encoded_frames = self.input_frames.saturating_sub(self.skipped_frames);
+        per_second(f64::from(encoded_frames), self.elapsed)
+    }
+
+    pub fn bitrate(&self) -> f64 {
+        per_second(self.encoded_bytes as f64 * 8.0, self.elapsed)
+    }
+}
+
+impl DecoderStatsDelta {
+    pub fn frame_rate(&self) -> f64 {
+        per_second(f64::from(self.decoded_frames), self.elapsed)
+    }
+}
+
+impl fmt::Display for EncoderStatsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}fps {:.0}bps frames={} skipped={} idr={} ltr={}",
+            self.frame_rate(),
+            self.bitrate(),
+            self.input_frames,
+            self.skipped_frames,
+            self.idr_sent,
+            self.ltr_sent
+        )
+    }
+}
+
+impl fmt::Display for DecoderStatsDelta {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1}fps frames={} concealed={} idr_lost={}",
+            self.frame_rate(),
+            self.decoded_frames,
+            self.concealed_frames,
+            self.idr_lost
+        )
+    }
+}
+
+/// Statistics with running counters a `StatsSampler` can diff.
+pub trait Stats: Copy {
+    type Delta;
+
+    /// Counters that went backwards, e.g. after a re-initialization, count as zero.
+    fn delta_since(&self, earlier: &Self, elapsed: Duration) -> Self::Delta;
+}
+
+impl Stats for EncoderStats {
+    type Delta = EncoderStatsDelta;
+
+    fn delta_since(&self, earlier: &Self, elapsed: Duration) -> EncoderStatsDelta {
+        EncoderStatsDelta {
+            elapsed,
+            input_frames: self
+                .input_frame_count
+                .saturating_sub(earlier.input_frame_count),
+            skipped_frames: self
+                .skipped_frame_count
+                .saturating_sub(earlier.skipped_frame_count),
+            idr_sent: self.idr_sent_count.saturating_sub(earlier.idr_sent_count),
+            ltr_sent: self.ltr_sent_count.saturating_sub(earlier.ltr_sent_count),
+            encoded_bytes: self
+                .total_encoded_bytes
+                .saturating_sub(earlier.total_encoded_bytes),
+        }
+    }
+}
+
+impl Stats for DecoderStats {
+    type Delta = DecoderStatsDelta;
+
+    fn delta_since(&self, earlier: &Self, elapsed: Duration) -> DecoderStatsDelta {
+        DecoderStatsDelta {
+            elapsed,
+            decoded_frames: self
+                .decoded_frame_count
+                .saturating_sub(earlier.decoded_frame_count),
+            concealed_frames: self
+                .concealed_frame_count
+                .saturating_sub(earlier.concealed_frame_count),
+            idr_lost: self.idr_lost_count.saturating_sub(earlier.idr_lost_count),
+        }
+    }
+}
+
+/// Emits a delta every time at least `interval` has passed since the previous one.
+pub struct StatsSampler<S: Stats> {
+    interval: Duration,
+    last: Option<(Instant, S)>,
+}
+
+impl<S: Stats> StatsSampler<S> {
+    pub fn new(interval: Duration) -> Self {
+        StatsSampler {
+            interval,
+            last: None,
+        }
+    }
+
+    /// The first call only records a baseline and returns `None`.
+    pub fn sample(&mut self, now: Instant, stats: S) -> Option<S::Delta> {
+        match self.last {
+            Some((at, ref earlier)) if now.duration_since(at) >= self.interval => {
+                let delta = stats.delta_since(earlier, now.duration_since(at));
+                self.last = Some((now, stats));
+                Some(delta)
+            }
+            Some(_) => None,
+            None => {
+                self.last = Some((now, stats));
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,239 @@
+//! Typed description of a spatial/temporal SVC layer layout, written into `SEncParamExt`.
+//!
+//! Spatial layers are listed from the lowest resolution up, like `sSpatialLayers`. With
+//! `temporal_layers` set to `n`, openh264 encodes a dyadic hierarchy whose frames carry
+//! `uiTemporalId` `0..n`; dropping the frames above temporal id `t` halves the frame rate
+//! `n - 1 - t` times.
+
+use std::os::raw::c_int;
+
+use error::{Error, Result};
+use {
+    EProfileIdc, SEncParamExt, MAX_SPATIAL_LAYER_NUM, MAX_TEMPORAL_LAYER_NUM, PRO_BASELINE,
+    UNSPECIFIED_BIT_RATE,
+};
+
+/// One entry of `sSpatialLayers`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpatialLayer {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: f32,
+    /// Target bitrate in bits per second.
+    pub bitrate: u32,
+    /// Bitrate cap in bits per second, unset by default.
+    pub max_bitrate: Option<u32>,
+    pub profile: EProfileIdc,
+}
+
+/// Spatial and temporal layer configuration of an encoder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvcLayout {
+    /// `1..=MAX_SPATIAL_LAYER_NUM` layers, lowest resolution first.
+    pub spatial_layers: Vec<SpatialLayer>,
+    /// `1..=MAX_TEMPORAL_LAYER_NUM`.
+    pub temporal_layers: u32,
+    /// Encode every spatial layer as an independent AVC stream (`bSimulcastAVC`) instead of
+    /// predicting it from the layer below.
+    pub simulcast: bool,
+}
+
+impl SpatialLayer {
+    pub fn new(width: u32, height: u32, frame_rate: f32, bitrate: u32) -> Self {
+        SpatialLayer {
+            width,
+            height,
+            frame_rate,
+            bitrate,
+            max_bitrate: None,
+            profile: PRO_BASELINE,
+        }
+    }
+
+    fn validate(&self, index: usize) -> Result<()> {
+        if self.width == 0 || self.height == 0 || (self.width | self.height) & 1 != 0 {
+            return Err(invalid(format!(
+                "Spatial layer {} has size {}x{}, which is not a non-zero multiple of 2",
+                index, self.width, self.height
+            )));
+        }
+        if self.width > c_int::MAX as u32 || self.height > c_int::MAX as u32 {
+            return Err(invalid(format!(
+                "Spatial layer {} size {}x{} is too large",
+                index, self.width, self.height
+            )));
+        }
+        if !self.frame_rate.is_finite() || self.frame_rate <= 0.0 {
+            return Err(invalid(format!(
+                "Spatial layer {} frame rate must be positive and finite, got {}",
+                index, self.frame_rate
+            )));
+        }
+        if self.bitrate == 0 {
+            return Err(invalid(format!("Spatial layer {} bitrate is 0", index)));
+        }
+        match self.max_bitrate {
+            Some(max) if max < self.bitrate => Err(invalid(format!(
+                "Spatial layer {} max bitrate {} is below its bitrate {}",
+                index, max, self.bitrate
+            ))),
+            Some(max) if max > i32::MAX as u32 => Err(invalid(format!(
+                "Spatial layer {} max bitrate {} is too large",
+                index, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn invalid(message: String) -> Error {
+    Error::InvalidArgument(message)
+}
+
+impl SvcLayout {
+    /// A layout with a single temporal layer that predicts across spatial layers.
+    pub fn new(spatial_layers: Vec<SpatialLayer>) -> Self {
+        SvcLayout {
+            spatial_layers,
+            temporal_layers: 1,
+            simulcast: false,
+        }
+    }
+
+    /// Checks the layout against what openh264 accepts:
+    ///
+    /// - each layer is at least as large as the one below it in both dimensions, and in
+    ///   SVC mode at most twice as large;
+    /// - each layer's frame rate is the top layer's divided by a power of two that the
+    ///   temporal hierarchy can produce;
+    /// - frame rates are positive and finite;
+    /// - bitrates are non-zero, at most `max_bitrate`, and sum to less than `i32::MAX`,
+    ///   which `max_bitrate` must not exceed either.
+    pub fn validate(&self) -> Result<()> {
+        let count = self.spatial_layers.len();
+        if count == 0 || count > MAX_SPATIAL_LAYER_NUM as usize {
+            return Err(invalid(format!(
+                "Spatial layer count {} is out of range 1..={}",
+                count, MAX_SPATIAL_LAYER_NUM
+            )));
+        }
+        if self.temporal_layers == 0 || self.temporal_layers > MAX_TEMPORAL_LAYER_NUM {
+            return Err(invalid(format!(
+                "Temporal layer count {} is out of range 1..={}",
+                self.temporal_layers, MAX_TEMPORAL_LAYER_NUM
+            )));
+        }
+        for (index, layer) in self.spatial_layers.iter().enumerate() {
+            layer.validate(index)?;
+        }
+        for (index, pair) in self.spatial_layers.windows(2).enumerate() {
+            let (lower, upper) = (&pair[0], &pair[1]);
+            if upper.width < lower.width || upper.height < lower.height {
+                return Err(invalid(format!(
+                    "Spatial layer {} ({}x{}) is smaller than layer {} ({}x{})",
+                    index + 1,
+                    upper.width,
+                    upper.height,
+                    index,
+                    lower.width,
+                    lower.height
+                )));
+            }
+            if !self.simulcast && (upper.width > 2 * lower.width || upper.height > 2 * lower.height)
+            {
+                return Err(invalid(format!(
+                    "Spatial layer {} ({}x{}) is more than twice the size of layer {} ({}x{})",
+                    index + 1,
+                    upper.width,
+                    upper.height,
+                    index,
+                    lower.width,
+                    lower.height
+                )));
+            }
+        }
+        let max_frame_rate = self.max_frame_rate();
+        for (index, layer) in self.spatial_layers.iter().enumerate() {
+            let ratio = max_frame_rate / layer.frame_rate;
+            let decimation = ratio.log2().round();
+            if (ratio - decimation.exp2()).abs() > 0.01 * ratio
+                || decimation >= self.temporal_layers as f32
+            {
+                return Err(invalid(format!(
+                    "Spatial layer {} frame rate {} can't be derived from {} with {} temporal layers",
+                    index, layer.frame_rate, max_frame_rate, self.temporal_layers
+                )));
+            }
+        }
+        if self.total_bitrate() > i64::from(i32::MAX) {
+            return Err(invalid(format!(
+                "Total bitrate {} is too large",
+                self.total_bitrate()
+            )));
+        }
+        Ok(())
+    }
+
+    /// The highest layer frame rate, which the input is expected at.
+    pub fn max_frame_rate(&self) -> f32 {
+        self.spatial_layers
+            .iter()
+            .map(|layer| layer.frame_rate)
+            .fold(0.0, f32::max)
+    }
+
+    pub fn total_bitrate(&self) -> i64 {
+        self.spatial_layers
+            .iter()
+            .map(|layer| i64::from(layer.bitrate))
+            .sum()
+    }
+
+    /// Validates the layout and writes it into `param`, leaving unrelated fields alone.
+    /// The picture size becomes that of the top spatial layer.
+    pub fn apply(&self, param: &mut SEncParamExt) -> Result<()> {
+        self.validate()?;
+        let top = self.spatial_layers[self.spatial_layers.len() - 1];
+        param.iPicWidth = top.width as c_int;
+        param.iPicHeight = top.height as c_int;
+        param.fMaxFrameRate = self.max_frame_rate();
+        param.iTargetBitrate = self.total_bitrate() as c_int;
+        param.iSpatialLayerNum = self.spatial_layers.len() as c_int;
+        param.iTemporalLayerNum = self.temporal_layers as c_int;
+        param.bSimulcastAVC = self.simulcast;
+        for (config, layer) in param.sSpatialLayers.iter_mut().zip(&self.spatial_layers) {
+            config.iVideoWidth = layer.width as c_int;
+            config.iVideoHeight = layer.height as c_int;
+            config.fFrameRate = layer.frame_rate;
+            config.iSpatialBitrate = layer.bitrate as c_int;
+            config.iMaxSpatialBitrate = layer
+                .max_bitrate
+                .map_or(UNSPECIFIED_BIT_RATE as c_int, |max| max as c_int);
+            config.uiProfileIdc = layer.profile;
+        }
+        Ok(())
+    }
+
+    /// Reads the layout back out of `param`, e.g. from `Encoder::params()`.
+    pub fn from_params(param: &SEncParamExt) -> Self {
+        let count = (param.iSpatialLayerNum.max(0) as usize).min(param.sSpatialLayers.len());
+        SvcLayout {
+            spatial_layers: param.sSpatialLayers[..count]
+                .iter()
+                .map(|config| SpatialLayer {
+                    width: config.iVideoWidth as u32,
+                    height: config.iVideoHeight as u32,
+                    frame_rate: config.fFrameRate,
+                    bitrate: config.iSpatialBitrate as u32,
+                    max_bitrate: match config.iMaxSpatialBitrate {
+                        max if max <= UNSPECIFIED_BIT_RATE as c_int => None,
+                        max => Some(max as u32),
+                    },
+                    profile: config.uiProfileIdc,
+                })
+                .collect(),
+            temporal_layers: param.iTemporalLayerNum as u32,
+            simulcast: param.bSimulcastAVC,
+        }
+    }
+}
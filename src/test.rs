@@ -2,6 +2,7 @@ use super::*;
 use std::os::raw::{c_int, c_void};
 use std::ptr::null_mut;
 use std::slice::from_raw_parts;
+use std::time::{Duration, Instant};
 
 #[test]
 fn encode() {
@@ -65,6 +66,10 @@ fn encode() {
         u_input.resize((width / 2) * (height / 2), 0);
         let mut v_input = Vec::new();
         v_input.resize((width / 2) * (height / 2), 0);
+        let source = testsrc::TestSource::new(testsrc::Pattern::Gradient, width, height).frame(0);
+        y_input.copy_from_slice(&source.planes[0]);
+        u_input.copy_from_slice(&source.planes[1]);
+        v_input.copy_from_slice(&source.planes[2]);
 
         let mut info = SFrameBSInfo::default();
         let mut pic = SSourcePicture::default();
@@ -78,9 +83,7 @@ fn encode() {
         pic.pData[1] = u_input.as_mut_ptr();
         pic.pData[2] = v_input.as_mut_ptr();
 
-        if true {
-            assert_eq!((**encoder).ForceIntraFrame.unwrap()(encoder, true), 0);
-        }
+        assert_eq!((**encoder).ForceIntraFrame.unwrap()(encoder, true), 0);
 
         assert_eq!(
             (**encoder).EncodeFrame.unwrap()(encoder, &mut pic, &mut info),
@@ -122,6 +125,13 @@ fn encode() {
 
         assert!(out.len() > 0);
 
+        let mut decoder = Decoder::new().unwrap();
+        decoder.initialize(&SDecodingParam::default()).unwrap();
+        let decoded = decoder.decode(&out).unwrap().unwrap();
+        let quality = metrics::compare(&source, &decoded).unwrap();
+        assert!(quality.psnr.y > 35.0, "{:?}", quality);
+        assert!(quality.ssim.y > 0.95, "{:?}", quality);
+
         WelsDestroySVCEncoder(encoder);
     }
 }
@@ -138,3 +148,1580 @@ fn decode() {
         WelsDestroyDecoder(decoder);
     }
 }
+
+fn camera_param(encoder: &Encoder, width: usize, height: usize) -> SEncParamExt {
+    let mut param = encoder.default_params().unwrap();
+    let fps = 30.0;
+    let bitrate = 500_000;
+    param.iUsageType = CAMERA_VIDEO_REAL_TIME;
+    param.fMaxFrameRate = fps;
+    param.iMaxBitrate = UNSPECIFIED_BIT_RATE as i32;
+    param.iSpatialLayerNum = 1;
+    param.sSpatialLayers[0].uiProfileIdc = PRO_BASELINE;
+    param.sSpatialLayers[0].iVideoWidth = width as i32;
+    param.sSpatialLayers[0].iVideoHeight = height as i32;
+    param.sSpatialLayers[0].fFrameRate = fps;
+    param.sSpatialLayers[0].iSpatialBitrate = bitrate;
+    param.sSpatialLayers[0].iMaxSpatialBitrate = UNSPECIFIED_BIT_RATE as i32;
+    param.iPicWidth = width as i32;
+    param.iPicHeight = height as i32;
+    param.iTargetBitrate = bitrate;
+    param
+}
+
+fn source_picture(width: usize, height: usize, planes: &mut [Vec<u8>; 3]) -> SSourcePicture {
+    SSourcePicture {
+        iPicWidth: width as i32,
+        iPicHeight: height as i32,
+        iColorFormat: videoFormatI420 as i32,
+        iStride: [width as i32, (width / 2) as i32, (width / 2) as i32, 0],
+        pData: [
+            planes[0].as_mut_ptr(),
+            planes[1].as_mut_ptr(),
+            planes[2].as_mut_ptr(),
+            null_mut(),
+        ],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn ltr_message_bytes() {
+    let request = ltr::LtrRecoveryRequest::ltr(3, 41, -2);
+    let bytes = request.to_bytes();
+    assert_eq!(ltr::LtrRecoveryRequest::from_bytes(&bytes), Ok(request));
+    assert!(ltr::LtrRecoveryRequest::from_bytes(&bytes[1..]).is_err());
+
+    let feedback = ltr::LtrMarkingFeedback {
+        success: false,
+        idr_pic_id: 7,
+        ltr_frame_num: 12,
+        layer_id: 0,
+    };
+    let mut bytes = feedback.to_bytes();
+    assert_eq!(ltr::LtrMarkingFeedback::from_bytes(&bytes), Ok(feedback));
+    bytes[0] = 0xff;
+    assert!(ltr::LtrMarkingFeedback::from_bytes(&bytes).is_err());
+}
+
+#[test]
+fn ltr_feedback_loop() {
+    let width = 64;
+    let height = 64;
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, width, height);
+    param.bEnableLongTermReference = true;
+    param.iLTRRefNum = 1;
+    encoder.initialize(&param).unwrap();
+
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+
+    let mut planes = [
+        vec![0; width * height],
+        vec![128; width * height / 4],
+        vec![128; width * height / 4],
+    ];
+    let mut feedback_count = 0;
+    for frame in 0..10 {
+        for (i, sample) in planes[0].iter_mut().enumerate() {
+            *sample = ((i + frame * 7) % 256) as u8;
+        }
+        let pic = source_picture(width, height, &mut planes);
+        let encoded = unsafe { encoder.encode_frame(&pic) }.unwrap();
+        let bytes = encoded.to_bytes();
+        if bytes.is_empty() {
+            continue;
+        }
+        decoder.decode(&bytes).unwrap();
+        if let Some(feedback) = decoder.ltr_marking_feedback().unwrap() {
+            let received = ltr::LtrMarkingFeedback::from_bytes(&feedback.to_bytes()).unwrap();
+            assert_eq!(received, feedback);
+            encoder.ltr_marking_feedback(&received).unwrap();
+            feedback_count += 1;
+        }
+    }
+    assert!(feedback_count > 0);
+
+    let request = decoder
+        .ltr_recovery_request(decoder.frame_num().unwrap() - 1)
+        .unwrap();
+    let received = ltr::LtrRecoveryRequest::from_bytes(&request.to_bytes()).unwrap();
+    assert_eq!(received, request);
+    encoder.ltr_recovery_request(&received).unwrap();
+
+    let pic = source_picture(width, height, &mut planes);
+    let encoded = unsafe { encoder.encode_frame(&pic) }.unwrap();
+    decoder.decode(&encoded.to_bytes()).unwrap();
+}
+
+#[test]
+fn concealed_frames() {
+    let mut encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, 64, 64);
+    encoder.initialize(&param).unwrap();
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    decoder
+        .set_option(option::decoder::ERROR_CON_IDC, ERROR_CON_SLICE_COPY as c_int)
+        .unwrap();
+
+    let source = testsrc::TestSource::new(testsrc::Pattern::ScrollingText, 64, 64);
+    let mut encoded = (0..3).map(|index| {
+        let frame = source.frame(index);
+        encoder.encode(&frame.as_yuv().unwrap()).unwrap().to_bytes()
+    });
+    let decoded = decoder.decode(&encoded.next().unwrap()).unwrap().unwrap();
+    assert!(!decoded.is_concealed());
+
+    // The second frame is lost, so the third one references a missing picture.
+    encoded.next();
+    let decoded = decoder.decode(&encoded.next().unwrap()).unwrap().unwrap();
+    assert!(decoded.is_concealed());
+    assert_eq!((decoded.width, decoded.height), (64, 64));
+}
+
+#[test]
+fn reconfigure_resolution() {
+    let mut encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, 64, 64);
+    encoder.initialize(&param).unwrap();
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+
+    encoder.set_bitrate(Layer::Spatial(0), 300_000).unwrap();
+    encoder.set_bitrate(Layer::All, 300_000).unwrap();
+    encoder.set_max_bitrate(Layer::All, 600_000).unwrap();
+    encoder.set_frame_rate(15.0).unwrap();
+    assert!(encoder.set_bitrate(Layer::Spatial(4), 300_000).is_err());
+    assert!(encoder.set_frame_rate(0.0).is_err());
+    assert!(encoder.set_frame_rate(f32::INFINITY).is_err());
+    assert_eq!(encoder.reconfigure(&encoder.params().unwrap()), Ok(false));
+
+    for &(width, height) in &[(64, 64), (96, 48)] {
+        let param = camera_param(&encoder, width, height);
+        let layout_changed = encoder.reconfigure(&param).unwrap();
+        assert_eq!(layout_changed, width != 64);
+        let mut planes = [
+            vec![0; width * height],
+            vec![128; width * height / 4],
+            vec![128; width * height / 4],
+        ];
+        for frame in 0..3 {
+            for (i, sample) in planes[0].iter_mut().enumerate() {
+                *sample = ((i * 3 + frame * 5) % 256) as u8;
+            }
+            let pic = source_picture(width, height, &mut planes);
+            let encoded = unsafe { encoder.encode_frame(&pic) }.unwrap();
+            if frame == 0 && layout_changed {
+                assert_eq!(encoded.frame_type, videoFrameTypeIDR);
+            }
+            if let Some(decoded) = decoder.decode(&encoded.to_bytes()).unwrap() {
+                assert_eq!((decoded.width, decoded.height), (width, height));
+            }
+        }
+    }
+}
+
+#[test]
+fn typed_options() {
+    let mut encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, 64, 64);
+    encoder.initialize(&param).unwrap();
+
+    assert_eq!(
+        encoder.get_option(option::encoder::DATAFORMAT).unwrap(),
+        videoFormatI420 as c_int
+    );
+    encoder
+        .set_option(option::encoder::RC_FRAME_SKIP, false)
+        .unwrap();
+    encoder.set_option(option::encoder::IDR_INTERVAL, 30).unwrap();
+    encoder
+        .set_option(
+            option::encoder::PROFILE,
+            SProfileInfo {
+                iLayer: 0,
+                uiProfileIdc: PRO_BASELINE,
+            },
+        )
+        .unwrap();
+    let current = encoder
+        .get_option(option::encoder::SVC_ENCODE_PARAM_EXT)
+        .unwrap();
+    assert_eq!((current.iPicWidth, current.iPicHeight), (64, 64));
+    let statistics = encoder.get_option(option::encoder::GET_STATISTICS).unwrap();
+    assert_eq!(statistics.uiInputFrameCount, 0);
+    let layer_bitrate = encoder
+        .get_option_with(
+            option::encoder::BITRATE,
+            SBitrateInfo {
+                iLayer: SPATIAL_LAYER_0,
+                iBitrate: 0,
+            },
+        )
+        .unwrap();
+    assert_eq!(layer_bitrate.iLayer, SPATIAL_LAYER_0);
+    assert_eq!(layer_bitrate.iBitrate, current.sSpatialLayers[0].iSpatialBitrate);
+
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    decoder
+        .set_option(option::decoder::ERROR_CON_IDC, ERROR_CON_DISABLE as c_int)
+        .unwrap();
+    assert_eq!(
+        decoder.get_option(option::decoder::ERROR_CON_IDC).unwrap(),
+        ERROR_CON_DISABLE as c_int
+    );
+    let statistics = decoder.get_option(option::decoder::GET_STATISTICS).unwrap();
+    assert_eq!(statistics.uiDecodedFrameCount, 0);
+}
+
+#[test]
+fn stats_sampler() {
+    let start = Instant::now();
+    let mut sampler = stats::StatsSampler::new(Duration::from_secs(1));
+    let mut stats = stats::EncoderStats::default();
+    assert_eq!(sampler.sample(start, stats), None);
+
+    stats.input_frame_count = 30;
+    stats.skipped_frame_count = 5;
+    stats.total_encoded_bytes = 12_500;
+    assert_eq!(sampler.sample(start + Duration::from_millis(500), stats), None);
+
+    let delta = sampler
+        .sample(start + Duration::from_millis(1250), stats)
+        .unwrap();
+    assert_eq!(delta.input_frames, 30);
+    assert_eq!(delta.skipped_frames, 5);
+    assert_eq!(delta.frame_rate(), 20.0);
+    assert_eq!(delta.bitrate(), 80_000.0);
+
+    stats.input_frame_count = 0;
+    let delta = sampler
+        .sample(start + Duration::from_millis(2250), stats)
+        .unwrap();
+    assert_eq!(delta.input_frames, 0);
+}
+
+#[test]
+fn encode_decode_stats() {
+    let width = 64;
+    let height = 64;
+    let mut encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, width, height);
+    encoder.initialize(&param).unwrap();
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+
+    let mut planes = [
+        vec![0; width * height],
+        vec![128; width * height / 4],
+        vec![128; width * height / 4],
+    ];
+    for frame in 0..5 {
+        for (i, sample) in planes[0].iter_mut().enumerate() {
+            *sample = ((i + frame * 11) % 256) as u8;
+        }
+        let pic = source_picture(width, height, &mut planes);
+        let encoded = unsafe { encoder.encode_frame(&pic) }.unwrap();
+        decoder.decode(&encoded.to_bytes()).unwrap();
+    }
+
+    let encoder_stats = encoder.stats().unwrap();
+    assert_eq!(encoder_stats.input_frame_count, 5);
+    assert!(encoder_stats.idr_sent_count >= 1);
+    assert!(!encoder_stats.to_string().is_empty());
+    let decoder_stats = decoder.stats().unwrap();
+    assert!(decoder_stats.decoded_frame_count > 0);
+    assert!(!decoder_stats.to_string().is_empty());
+}
+
+#[cfg(feature = "log")]
+mod log_capture {
+    use log::{Level, LevelFilter, Log, Metadata, Record};
+    use std::sync::Mutex;
+
+    pub struct CaptureLogger {
+        pub records: Mutex<Vec<(String, Level, String)>>,
+    }
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            self.records.lock().unwrap().push((
+                record.target().to_owned(),
+                record.level(),
+                record.args().to_string(),
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    pub static LOGGER: CaptureLogger = CaptureLogger {
+        records: Mutex::new(Vec::new()),
+    };
+
+    pub fn install() {
+        let _ = log::set_logger(&LOGGER);
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn trace_to_log() {
+    log_capture::install();
+    let mut encoder = Encoder::new().unwrap();
+    let other = Encoder::new().unwrap();
+    assert_ne!(encoder.log_target(), other.log_target());
+    assert!(encoder.log_target().starts_with("openh264::encoder#"));
+
+    let mut param = camera_param(&encoder, 64, 64);
+    param.iPicWidth = 0;
+    param.sSpatialLayers[0].iVideoWidth = 0;
+    assert!(encoder.initialize(&param).is_err());
+
+    let records = log_capture::LOGGER.records.lock().unwrap();
+    assert!(records
+        .iter()
+        .any(|record| record.0 == encoder.log_target() && record.1 == log::Level::Error));
+    assert!(records.iter().all(|record| record.0 != other.log_target()));
+}
+
+#[test]
+fn parameter_sets_and_delayed_decode() {
+    let width = 64;
+    let height = 64;
+    let mut encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, width, height);
+    encoder.initialize(&param).unwrap();
+    let parameter_sets = encoder.encode_parameter_sets().unwrap();
+    assert!(parameter_sets.size() > 0);
+    assert_eq!(parameter_sets.size(), parameter_sets.to_bytes().len());
+
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    assert!(decoder
+        .decode_frame2(&parameter_sets.to_bytes())
+        .unwrap()
+        .is_none());
+
+    let mut planes = [
+        vec![64; width * height],
+        vec![128; width * height / 4],
+        vec![128; width * height / 4],
+    ];
+    let pic = source_picture(width, height, &mut planes);
+    let encoded = unsafe { encoder.encode_frame(&pic) }.unwrap();
+    let mut decoded = 0;
+    if decoder.decode_frame2(&encoded.to_bytes()).unwrap().is_some() {
+        decoded += 1;
+    }
+    if decoder.decode_frame2(&[]).unwrap().is_some() {
+        decoded += 1;
+    }
+    assert_eq!(decoded, 1);
+}
+
+#[test]
+fn separated_parameter_sets() {
+    let nal_types = |sets: &[(u8, Vec<u8>)]| -> Vec<(u8, u8)> {
+        sets.iter()
+            .map(|set| (set.0, nal::nal_units(&set.1).next().unwrap().nal_type()))
+            .collect()
+    };
+
+    let mut encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, 64, 64);
+    encoder.initialize(&param).unwrap();
+    let sets = encoder.parameter_sets().unwrap();
+    assert_eq!(nal_types(&sets.sps), [(0, nal::NAL_SPS)]);
+    assert_eq!(nal_types(&sets.pps), [(0, nal::NAL_PPS)]);
+    assert_eq!(
+        sets.to_bytes(),
+        [sets.sps_bytes(), sets.pps_bytes()].concat()
+    );
+
+    let layout = svc::SvcLayout::new(vec![
+        svc::SpatialLayer::new(64, 64, 30.0, 100_000),
+        svc::SpatialLayer::new(128, 128, 30.0, 300_000),
+    ]);
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, 128, 128);
+    layout.apply(&mut param).unwrap();
+    encoder.initialize(&param).unwrap();
+    let sets = encoder.parameter_sets().unwrap();
+    assert_eq!(
+        nal_types(&sets.sps),
+        [(0, nal::NAL_SPS), (1, nal::NAL_SUBSET_SPS)]
+    );
+    assert_eq!(nal_types(&sets.pps), [(0, nal::NAL_PPS), (1, nal::NAL_PPS)]);
+    let base = sets.layer(0);
+    assert_eq!((base.sps.len(), base.pps.len()), (1, 1));
+    assert!(sets.layer(2).to_bytes().is_empty());
+
+    // A decoder that only sees the parameter sets out of band decodes a later IDR.
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    decoder.decode(&base.to_bytes()).unwrap();
+    let source = testsrc::TestSource::new(testsrc::Pattern::Gradient, 128, 128).frame(0);
+    encoder.set_prepend_parameter_sets(true);
+    encoder.force_intra_frame(true).unwrap();
+    let frame = encoder.encode(&source.as_yuv().unwrap()).unwrap();
+    assert_eq!(frame.frame_type, videoFrameTypeIDR);
+    let first = &frame.layers[0];
+    assert!(!first.video_coding);
+    assert_eq!(
+        nal::nal_units(&first.nals[0]).next().unwrap().nal_type(),
+        nal::NAL_SPS
+    );
+    let slices: Vec<u8> = frame
+        .layers
+        .iter()
+        .filter(|layer| layer.video_coding && layer.spatial_id == 0)
+        .flat_map(|layer| layer.nals.concat())
+        .collect();
+    let decoded = decoder.decode(&slices).unwrap().unwrap();
+    assert_eq!((decoded.width, decoded.height), (64, 64));
+}
+
+fn idr_indices(
+    policy: keyframe::KeyframePolicy,
+    pattern: testsrc::Pattern,
+    frames: usize,
+    before_frame: &mut dyn FnMut(&mut Encoder, usize),
+) -> Vec<usize> {
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, 64, 64);
+    policy.apply(&mut param).unwrap();
+    encoder.initialize(&param).unwrap();
+    assert_eq!(
+        keyframe::KeyframePolicy::from_params(&encoder.params().unwrap()),
+        policy
+    );
+
+    let source = testsrc::TestSource::new(pattern, 64, 64);
+    let mut idrs = Vec::new();
+    for index in 0..frames {
+        before_frame(&mut encoder, index);
+        let frame = source.frame(index);
+        if encoder.encode(&frame.as_yuv().unwrap()).unwrap().is_idr() {
+            idrs.push(index);
+        }
+    }
+    idrs
+}
+
+#[test]
+fn keyframe_policy() {
+    use keyframe::KeyframePolicy;
+    use testsrc::Pattern;
+
+    let policy = |idr_interval, scene_change_detection| KeyframePolicy {
+        idr_interval,
+        scene_change_detection,
+    };
+    let cuts = Pattern::SceneCuts { interval: 10 };
+    let mut nothing = |_: &mut Encoder, _| {};
+
+    assert_eq!(idr_indices(policy(0, false), cuts, 30, &mut nothing), [0]);
+    assert_eq!(
+        idr_indices(policy(0, true), cuts, 30, &mut nothing),
+        [0, 10, 20]
+    );
+    assert_eq!(
+        idr_indices(policy(8, false), Pattern::Gradient, 24, &mut nothing),
+        [0, 8, 16]
+    );
+    assert_eq!(
+        idr_indices(
+            policy(0, false),
+            Pattern::Gradient,
+            24,
+            &mut |encoder, index| {
+                if index == 0 {
+                    encoder.set_idr_interval(6).unwrap();
+                }
+            }
+        ),
+        [0, 6, 12, 18]
+    );
+
+    // Requests from another thread and repeated requests each cost one IDR.
+    let requests = idr_indices(
+        policy(0, false),
+        Pattern::Gradient,
+        20,
+        &mut |encoder, index| match index {
+            5 => {
+                let requester = encoder.keyframe_requester();
+                std::thread::spawn(move || {
+                    requester.request();
+                    requester.request();
+                })
+                .join()
+                .unwrap();
+            }
+            12 => encoder.request_keyframe(),
+            _ => {}
+        },
+    );
+    assert_eq!(requests, [0, 5, 12]);
+
+    let mut encoder = Encoder::new().unwrap();
+    assert!(encoder.set_idr_interval(u32::MAX).is_err());
+}
+
+#[cfg(feature = "tracing")]
+mod span_capture {
+    use std::fmt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    #[derive(Default)]
+    pub struct Captured {
+        /// Span name and every `field=value` recorded on it, in creation order.
+        pub spans: Mutex<Vec<(&'static str, Vec<String>)>>,
+    }
+
+    pub struct CaptureSubscriber(pub Arc<Captured>, AtomicUsize);
+
+    impl CaptureSubscriber {
+        pub fn new(captured: Arc<Captured>) -> Self {
+            CaptureSubscriber(captured, AtomicUsize::new(1))
+        }
+    }
+
+    struct FieldVisitor<'a>(&'a mut Vec<String>);
+
+    impl<'a> Visit for FieldVisitor<'a> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+
+    impl Subscriber for CaptureSubscriber {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes) -> Id {
+            let mut fields = Vec::new();
+            attributes.record(&mut FieldVisitor(&mut fields));
+            let mut spans = self.0.spans.lock().unwrap();
+            spans.push((attributes.metadata().name(), fields));
+            self.1.fetch_add(1, Ordering::Relaxed);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record) {
+            let mut spans = self.0.spans.lock().unwrap();
+            let fields = &mut spans[span.into_u64() as usize - 1].1;
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event) {}
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn encode_decode_spans() {
+    use std::sync::Arc;
+
+    let captured = Arc::new(span_capture::Captured::default());
+    let subscriber = span_capture::CaptureSubscriber::new(captured.clone());
+    tracing::subscriber::with_default(subscriber, || {
+        let width = 64;
+        let height = 64;
+        let mut encoder = Encoder::new().unwrap();
+        let param = camera_param(&encoder, width, height);
+        encoder.initialize(&param).unwrap();
+        let mut decoder = Decoder::new().unwrap();
+        decoder.initialize(&SDecodingParam::default()).unwrap();
+
+        let mut planes = [
+            vec![64; width * height],
+            vec![128; width * height / 4],
+            vec![128; width * height / 4],
+        ];
+        let pic = source_picture(width, height, &mut planes);
+        let encoded = unsafe { encoder.encode_frame(&pic) }.unwrap();
+        encoder.encode_parameter_sets().unwrap();
+        decoder.decode(&encoded.to_bytes()).unwrap();
+        decoder.decode_frame2(&encoded.to_bytes()).unwrap();
+    });
+
+    let spans = captured.spans.lock().unwrap();
+    let names: Vec<_> = spans.iter().map(|span| span.0).collect();
+    assert_eq!(
+        names,
+        [
+            "EncodeFrame",
+            "EncodeParameterSets",
+            "DecodeFrameNoDelay",
+            "DecodeFrame2"
+        ]
+    );
+    assert!(spans[0].1.contains(&"frame_type=\"IDR\"".to_owned()));
+    assert!(spans[0].1.iter().any(|field| field.starts_with("bytes=")));
+    assert!(spans[2].1.contains(&"frame_ready=true".to_owned()));
+    assert!(spans[2].1.contains(&"width=64".to_owned()));
+}
+
+#[test]
+fn svc_layout_validation() {
+    let layer = |width, height, fps| svc::SpatialLayer::new(width, height, fps, 200_000);
+    let mut layout = svc::SvcLayout::new(vec![layer(160, 90, 15.0), layer(320, 180, 30.0)]);
+    assert!(layout.validate().is_err());
+    layout.temporal_layers = 2;
+    layout.validate().unwrap();
+
+    let mut param = SEncParamExt::default();
+    layout.apply(&mut param).unwrap();
+    assert_eq!((param.iPicWidth, param.iPicHeight), (320, 180));
+    assert_eq!(param.iSpatialLayerNum, 2);
+    assert_eq!(param.iTemporalLayerNum, 2);
+    assert_eq!(param.iTargetBitrate, 400_000);
+    assert_eq!(param.fMaxFrameRate, 30.0);
+    assert_eq!(svc::SvcLayout::from_params(&param), layout);
+
+    let too_far = svc::SvcLayout::new(vec![layer(160, 90, 30.0), layer(640, 360, 30.0)]);
+    assert!(too_far.validate().is_err());
+    let simulcast = svc::SvcLayout {
+        simulcast: true,
+        ..too_far
+    };
+    simulcast.validate().unwrap();
+
+    let descending = svc::SvcLayout::new(vec![layer(320, 180, 30.0), layer(160, 90, 30.0)]);
+    assert!(descending.validate().is_err());
+    let odd = svc::SvcLayout::new(vec![layer(161, 90, 30.0)]);
+    assert!(odd.validate().is_err());
+    let no_bitrate = svc::SvcLayout::new(vec![svc::SpatialLayer::new(160, 90, 30.0, 0)]);
+    assert!(no_bitrate.validate().is_err());
+    let mut uncapped = layer(160, 90, 30.0);
+    uncapped.max_bitrate = Some(u32::MAX);
+    assert!(svc::SvcLayout::new(vec![uncapped]).validate().is_err());
+    let unbounded = svc::SvcLayout::new(vec![layer(160, 90, f32::INFINITY)]);
+    assert!(unbounded.apply(&mut SEncParamExt::default()).is_err());
+    assert!(svc::SvcLayout::new(Vec::new()).validate().is_err());
+}
+
+#[test]
+fn simulcast_layers() {
+    let layout = svc::SvcLayout {
+        spatial_layers: vec![
+            svc::SpatialLayer::new(64, 64, 30.0, 100_000),
+            svc::SpatialLayer::new(128, 128, 30.0, 300_000),
+        ],
+        temporal_layers: 2,
+        simulcast: true,
+    };
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, 128, 128);
+    layout.apply(&mut param).unwrap();
+    encoder.initialize(&param).unwrap();
+
+    let mut decoders = Vec::new();
+    for _ in 0..3 {
+        let mut decoder = Decoder::new().unwrap();
+        decoder.initialize(&SDecodingParam::default()).unwrap();
+        decoders.push((decoder, 0));
+    }
+    // (spatial id, max temporal id, expected size) of each forwarded stream.
+    let streams = [(0, 1, 64), (1, 1, 128), (1, 0, 128)];
+
+    let mut planes = [vec![64; 128 * 128], vec![128; 64 * 64], vec![128; 64 * 64]];
+    for i in 0..4 {
+        let mut pic = source_picture(128, 128, &mut planes);
+        pic.uiTimeStamp = i * 33;
+        let frame = unsafe { encoder.encode_frame(&pic) }.unwrap();
+        let video_layers: Vec<_> = frame
+            .layers
+            .iter()
+            .filter(|layer| layer.video_coding)
+            .map(|layer| (layer.spatial_id, layer.temporal_id))
+            .collect();
+        let temporal_id = (i % 2) as u8;
+        assert_eq!(video_layers, [(0, temporal_id), (1, temporal_id)]);
+
+        for (&(spatial_id, max_temporal_id, size), decoder) in streams.iter().zip(&mut decoders)
+        {
+            let bytes = frame.simulcast_bytes(spatial_id, max_temporal_id);
+            if temporal_id > max_temporal_id {
+                assert!(bytes.is_empty());
+                continue;
+            }
+            let decoded = decoder.0.decode(&bytes).unwrap().unwrap();
+            assert_eq!((decoded.width, decoded.height), (size, size));
+            decoder.1 += 1;
+        }
+    }
+    let decoded_counts: Vec<_> = decoders.iter().map(|decoder| decoder.1).collect();
+    assert_eq!(decoded_counts, [4, 4, 2]);
+}
+
+#[test]
+fn nal_parsing() {
+    let stream = [
+        0, 0, 0, 1, 0x67, 0x42, 0xc0, 0x1e, // SPS
+        0, 0, 1, 0x6e, 0xc0, 0x80, 0x00, 0x80, // prefix NAL, temporal id 0
+        0, 0, 1, 0x65, 0x88, 0x80, 0x00, 0x00, // IDR slice, trailing zeros
+        0, 0, 0, 1, 0x4e, 0x80, 0x80, 0x20, 0x80, // prefix NAL, temporal id 1
+        0, 0, 1, 0x01, 0x9a, // non-IDR slice
+        0, 0, 1, 0x74, 0x80, 0x90, 0x40, 0x9a, // slice extension, dependency 1, temporal id 2
+    ];
+    let nals: Vec<_> = nal::nal_units(&stream).collect();
+    let types: Vec<_> = nals.iter().map(|nal| nal.nal_type()).collect();
+    assert_eq!(types, [7, 14, 5, 14, 1, 20]);
+    assert_eq!(nals[0].bytes(), &stream[..8]);
+    assert_eq!(nals[2].payload(), [0x65, 0x88, 0x80]);
+    assert_eq!(nals[4].ref_idc(), 0);
+    let svc = nals[5].svc_extension().unwrap();
+    assert_eq!((svc.dependency_id, svc.temporal_id), (1, 2));
+    assert!(nals[4].svc_extension().is_none());
+
+    let base = nal::extract_temporal_layers(&stream, 0);
+    let types: Vec<_> = nal::nal_units(&base).map(|nal| nal.nal_type()).collect();
+    assert_eq!(types, [7, 14, 5]);
+    assert_eq!(nals[1].svc_extension().unwrap().temporal_id, 0);
+    let types: Vec<_> = nal::nal_units(&nal::extract_temporal_layers(&stream, 1))
+        .map(|nal| nal.nal_type())
+        .collect();
+    assert_eq!(types, [7, 14, 5, 14, 1]);
+    assert_eq!(nal::extract_temporal_layers(&stream, 2), stream);
+}
+
+#[test]
+fn extract_temporal_layers() {
+    let layout = svc::SvcLayout {
+        spatial_layers: vec![
+            svc::SpatialLayer::new(64, 64, 30.0, 100_000),
+            svc::SpatialLayer::new(128, 128, 30.0, 300_000),
+        ],
+        temporal_layers: 3,
+        simulcast: false,
+    };
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, 128, 128);
+    layout.apply(&mut param).unwrap();
+    param.bPrefixNalAddingCtrl = true;
+    encoder.initialize(&param).unwrap();
+
+    let mut decoders = Vec::new();
+    for _ in 0..3 {
+        let mut decoder = Decoder::new().unwrap();
+        decoder.initialize(&SDecodingParam::default()).unwrap();
+        decoders.push((decoder, 0));
+    }
+    let mut planes = [vec![64; 128 * 128], vec![128; 64 * 64], vec![128; 64 * 64]];
+    for i in 0..8 {
+        let mut pic = source_picture(128, 128, &mut planes);
+        pic.uiTimeStamp = i * 33;
+        let frame = unsafe { encoder.encode_frame(&pic) }.unwrap();
+        let stream = frame.to_bytes();
+        assert!(nal::nal_units(&stream).any(|nal| nal.nal_type() == nal::NAL_PREFIX));
+
+        for (max_temporal_id, decoder) in decoders.iter_mut().enumerate() {
+            let max_temporal_id = max_temporal_id as u8;
+            let thinned = nal::extract_temporal_layers(&stream, max_temporal_id);
+            assert_eq!(thinned, frame.svc_bytes(1, max_temporal_id));
+            if thinned.is_empty() {
+                continue;
+            }
+            if let Some(decoded) = decoder.0.decode(&thinned).unwrap() {
+                assert_eq!((decoded.width, decoded.height), (128, 128));
+                decoder.1 += 1;
+            }
+        }
+    }
+    let decoded_counts: Vec<_> = decoders.iter().map(|decoder| decoder.1).collect();
+    assert_eq!(decoded_counts, [2, 4, 8]);
+}
+
+#[test]
+fn handles_are_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Encoder>();
+    assert_send::<Decoder>();
+    assert_send::<Frame>();
+}
+
+#[test]
+fn encoder_pool_order() {
+    let sizes = [(32, 32), (64, 64), (48, 48)];
+    let encoder = Encoder::new().unwrap();
+    let params: Vec<_> = sizes
+        .iter()
+        .map(|&(width, height)| camera_param(&encoder, width, height))
+        .collect();
+    let (pool, results) = pool::EncoderPool::new(&params, 2, 2).unwrap();
+    assert_eq!(pool.streams(), 3);
+    assert!(pool.submit(3, Frame::new(32, 32, 0)).is_err());
+
+    let frames_per_stream = 5;
+    let producer = std::thread::spawn(move || {
+        for i in 0..frames_per_stream {
+            for (stream, &(width, height)) in sizes.iter().enumerate() {
+                let frame = Frame::new(width, height, i * 33);
+                pool.submit(stream, frame).unwrap();
+            }
+        }
+    });
+
+    let mut timestamps = vec![Vec::new(); sizes.len()];
+    for output in results {
+        let frame = output.frame.unwrap();
+        if timestamps[output.stream].is_empty() {
+            assert_eq!(frame.frame_type, videoFrameTypeIDR);
+        }
+        timestamps[output.stream].push(frame.timestamp);
+    }
+    producer.join().unwrap();
+    let expected: Vec<i64> = (0..frames_per_stream).map(|i| i * 33).collect();
+    for stream_timestamps in timestamps {
+        assert_eq!(stream_timestamps, expected);
+    }
+}
+
+#[test]
+fn encoder_pool_drop_before_draining() {
+    let encoder = Encoder::new().unwrap();
+    let params = [camera_param(&encoder, 32, 32)];
+    let (pool, results) = pool::EncoderPool::new(&params, 1, 1).unwrap();
+    // More frames than the queue and the result channel hold together.
+    for i in 0..3 {
+        pool.submit(0, Frame::new(32, 32, i * 33)).unwrap();
+    }
+    drop(pool);
+    let timestamps: Vec<i64> = results
+        .iter()
+        .map(|output| output.frame.unwrap().timestamp)
+        .collect();
+    assert_eq!(timestamps, [0, 33, 66]);
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn async_encode_decode() {
+    use futures::executor::block_on;
+    use futures::{SinkExt, StreamExt};
+
+    let width = 64;
+    let height = 48;
+    let param = camera_param(&Encoder::new().unwrap(), width, height);
+    let mut encoder = AsyncEncoder::new(&param, 2).unwrap();
+    let mut decoder = AsyncDecoder::new(&SDecodingParam::default(), 2).unwrap();
+
+    for i in 0..5 {
+        let mut frame = Frame::new(width, height, i * 33);
+        frame.planes[0][..width].copy_from_slice(&[200; 64]);
+        block_on(encoder.send(frame)).unwrap();
+        let encoded = block_on(encoder.next()).unwrap().unwrap();
+        assert_eq!(encoded.timestamp, i * 33);
+        block_on(decoder.send(encoded.to_bytes())).unwrap();
+        let decoded = block_on(decoder.next()).unwrap().unwrap();
+        assert_eq!((decoded.width, decoded.height), (width, height));
+        assert_eq!(decoded.planes[0].len(), width * height);
+        assert_eq!(decoded.planes[1].len(), width * height / 4);
+    }
+    block_on(encoder.close()).unwrap();
+    assert!(block_on(encoder.next()).is_none());
+    block_on(decoder.close()).unwrap();
+    assert!(block_on(decoder.next()).is_none());
+
+    // Dropping with input still queued cancels without waiting for it.
+    let mut cancelled = AsyncEncoder::new(&param, 2).unwrap();
+    block_on(cancelled.send(Frame::new(width, height, 0))).unwrap();
+    drop(cancelled);
+}
+
+#[test]
+fn encoder_threads_slices() {
+    let layout = svc::SvcLayout::new(vec![
+        svc::SpatialLayer::new(160, 16, 30.0, 100_000),
+        svc::SpatialLayer::new(320, 32, 30.0, 200_000),
+        svc::SpatialLayer::new(640, 64, 30.0, 400_000),
+    ]);
+    let mut param = SEncParamExt::default();
+    layout.apply(&mut param).unwrap();
+    param.sSpatialLayers[2].sSliceArgument.uiSliceMode = SM_RASTER_SLICE;
+
+    threading::EncoderThreads::new(4).apply(&mut param).unwrap();
+    assert_eq!(param.iMultipleThreadIdc, 4);
+    let slices: Vec<_> = param.sSpatialLayers[..3]
+        .iter()
+        .map(|layer| (layer.sSliceArgument.uiSliceMode, layer.sSliceArgument.uiSliceNum))
+        .collect();
+    assert_eq!(
+        slices,
+        [
+            (SM_SINGLE_SLICE, 1),
+            (SM_FIXEDSLCNUM_SLICE, 2),
+            (SM_RASTER_SLICE, 0)
+        ]
+    );
+
+    threading::EncoderThreads::new(0).apply(&mut param).unwrap();
+    assert_eq!(param.sSpatialLayers[1].sSliceArgument.uiSliceNum, 0);
+    assert!(threading::EncoderThreads::new(MAX_THREADS_NUM as u16 + 1)
+        .apply(&mut param)
+        .is_err());
+}
+
+#[test]
+fn threaded_encode() {
+    let width = 64;
+    let height = 64;
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, width, height);
+    threading::EncoderThreads::new(2).apply(&mut param).unwrap();
+    encoder.initialize(&param).unwrap();
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+
+    let mut planes = [
+        vec![64; width * height],
+        vec![128; width * height / 4],
+        vec![128; width * height / 4],
+    ];
+    let pic = source_picture(width, height, &mut planes);
+    let encoded = unsafe { encoder.encode_frame(&pic) }.unwrap();
+    let slices = nal::nal_units(&encoded.to_bytes())
+        .filter(|nal| nal.nal_type() == nal::NAL_IDR_SLICE)
+        .count();
+    assert_eq!(slices, 2);
+    let decoded = decoder.decode(&encoded.to_bytes()).unwrap().unwrap();
+    assert_eq!((decoded.width, decoded.height), (width, height));
+}
+
+#[test]
+fn test_source_patterns() {
+    use testsrc::{Pattern, TestSource};
+
+    let patterns = [
+        Pattern::ColorBars,
+        Pattern::Gradient,
+        Pattern::Noise { seed: 7 },
+        Pattern::ScrollingText,
+        Pattern::SceneCuts { interval: 3 },
+    ];
+    for &pattern in &patterns {
+        let source = TestSource::new(pattern, 33, 17);
+        let frames: Vec<_> = source.clone().take(4).collect();
+        assert_eq!(frames[0].planes[0].len(), 33 * 17);
+        assert_eq!(frames[0].planes[1].len(), 17 * 9);
+        assert_eq!(frames[3], source.frame(3));
+        assert_eq!(frames[1].timestamp, 33);
+        assert!(frames[0].planes != frames[1].planes, "{:?} doesn't move", pattern);
+    }
+
+    let cuts = TestSource::new(Pattern::SceneCuts { interval: 3 }, 64, 64);
+    assert_eq!(cuts.frame(0), TestSource::new(Pattern::ColorBars, 64, 64).frame(0));
+    assert_eq!(cuts.frame(3), TestSource::new(Pattern::Gradient, 64, 64).frame(3));
+}
+
+#[test]
+fn test_source_exercises_encoder() {
+    use testsrc::{Pattern, TestSource};
+
+    let encoded_bytes = |frames: Vec<Frame>| {
+        let mut encoder = Encoder::new().unwrap();
+        let param = camera_param(&encoder, 64, 64);
+        encoder.initialize(&param).unwrap();
+        let mut bytes = 0;
+        for frame in frames {
+            bytes += encoder.encode(&frame.as_yuv().unwrap()).unwrap().size();
+        }
+        bytes
+    };
+    let flat = encoded_bytes((0..10).map(|i| Frame::new(64, 64, i * 33)).collect());
+    let noise = TestSource::new(Pattern::Noise { seed: 1 }, 64, 64);
+    assert!(encoded_bytes(noise.take(10).collect()) > 10 * flat);
+}
+
+#[test]
+fn metrics_scores() {
+    use testsrc::{Pattern, TestSource};
+
+    let reference = TestSource::new(Pattern::Gradient, 33, 17).frame(0);
+    fn view(planes: &[Vec<u8>; 3]) -> DecodedFrame<'_> {
+        DecodedFrame {
+            state: dsErrorFree,
+            width: 33,
+            height: 17,
+            timestamp: 0,
+            y: &planes[0],
+            u: &planes[1],
+            v: &planes[2],
+            y_stride: 33,
+            uv_stride: 17,
+        }
+    }
+
+    let identical = metrics::compare(&reference, &view(&reference.planes)).unwrap();
+    assert!(identical.psnr.y.is_infinite() && identical.psnr.v.is_infinite());
+    assert!((identical.ssim.y - 1.0).abs() < 1e-9);
+    assert!((identical.ssim.u - 1.0).abs() < 1e-9);
+
+    // A uniform error of 4 gives an MSE of 16.
+    let mut brighter = reference.planes.clone();
+    for sample in &mut brighter[0] {
+        *sample += 4;
+    }
+    let shifted = metrics::compare(&reference, &view(&brighter)).unwrap();
+    assert!((shifted.psnr.y - 36.09).abs() < 0.01, "{:?}", shifted);
+    assert!(shifted.psnr.u.is_infinite());
+    assert!(shifted.ssim.y < 1.0 && shifted.ssim.y > 0.9, "{:?}", shifted);
+
+    let mut cropped = view(&reference.planes);
+    cropped.height = 16;
+    assert!(metrics::compare(&reference, &cropped).is_err());
+}
+
+#[test]
+fn round_trip_quality() {
+    use testsrc::{Pattern, TestSource};
+
+    let encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, 64, 64);
+    let frames = TestSource::new(Pattern::Gradient, 64, 64).take(10);
+    let report = metrics::round_trip(&param, frames).unwrap();
+    assert_eq!(report.frames, 10);
+    assert_eq!(report.decoded_frames, 10);
+    assert!(report.encoded_bytes > 0);
+    assert!(report.bitrate > 0.0);
+    assert!(report.psnr.y > 30.0, "{:?}", report);
+    assert!(report.ssim.y > 0.9, "{:?}", report);
+}
+
+#[test]
+fn rate_control_modes() {
+    use rate_control::{FrameSkip, RateControl};
+    use testsrc::{Pattern, TestSource};
+
+    let (width, height, frames) = (176, 144, 90);
+    let bitrate = 150_000;
+    let encoder = Encoder::new().unwrap();
+    let run = |rate_control: RateControl| {
+        let mut param = camera_param(&encoder, width, height);
+        rate_control.apply(&mut param).unwrap();
+        assert_eq!(RateControl::from_params(&param).unwrap(), rate_control);
+        let source = TestSource::new(Pattern::Gradient, width, height).take(frames);
+        metrics::round_trip(&param, source).unwrap()
+    };
+
+    // The bitrate modes should land near the target once the first IDR is paid off.
+    let targeted = [
+        RateControl::Bitrate {
+            bitrate,
+            max_bitrate: None,
+            frame_skip: FrameSkip::Never,
+        },
+        RateControl::Bitrate {
+            bitrate,
+            max_bitrate: Some(2 * bitrate),
+            frame_skip: FrameSkip::BeforeEncoding,
+        },
+        RateControl::Bitrate {
+            bitrate,
+            max_bitrate: None,
+            frame_skip: FrameSkip::AfterEncoding,
+        },
+        RateControl::Timestamp {
+            bitrate,
+            max_bitrate: None,
+            frame_skip: true,
+        },
+        RateControl::Quality {
+            bitrate,
+            min_qp: 10,
+            max_qp: 40,
+        },
+    ];
+    for &rate_control in &targeted {
+        let report = run(rate_control);
+        let ratio = report.bitrate / f64::from(bitrate);
+        assert!(
+            ratio > 0.5 && ratio < 1.5,
+            "{:?} reached {:.0} bps",
+            rate_control,
+            report.bitrate
+        );
+    }
+
+    let buffer = run(RateControl::Buffer {
+        min_qp: 20,
+        max_qp: 36,
+    });
+    assert_eq!(buffer.decoded_frames, frames);
+    let fine = run(RateControl::ConstantQp { qp: 20 });
+    let coarse = run(RateControl::ConstantQp { qp: 40 });
+    assert_eq!(coarse.decoded_frames, frames);
+    assert!(fine.encoded_bytes > 2 * coarse.encoded_bytes);
+    assert!(fine.psnr.y > coarse.psnr.y);
+
+    let mut param = camera_param(&encoder, width, height);
+    assert!(RateControl::ConstantQp { qp: 52 }
+        .apply(&mut param)
+        .is_err());
+    assert!(RateControl::Buffer {
+        min_qp: 30,
+        max_qp: 20
+    }
+    .apply(&mut param)
+    .is_err());
+    assert!(RateControl::Bitrate {
+        bitrate,
+        max_bitrate: Some(bitrate - 1),
+        frame_skip: FrameSkip::Never,
+    }
+    .apply(&mut param)
+    .is_err());
+    assert!(RateControl::Timestamp {
+        bitrate: 0,
+        max_bitrate: None,
+        frame_skip: false,
+    }
+    .apply(&mut param)
+    .is_err());
+
+    let mut encoder = Encoder::new().unwrap();
+    encoder.initialize(&param).unwrap();
+    let constant = RateControl::ConstantQp { qp: 30 };
+    encoder.set_rate_control(&constant).unwrap();
+    let params = encoder.params().unwrap();
+    assert_eq!(RateControl::from_params(&params).unwrap(), constant);
+}
+
+#[test]
+fn encoding_presets() {
+    use preset::Preset;
+    use testsrc::{Pattern, TestSource};
+
+    let (width, height) = (320, 192);
+    for &preset in &Preset::ALL {
+        let mut encoder = Encoder::new().unwrap();
+        let param = preset
+            .params(&encoder, width as u32, height as u32, 30.0, 400_000)
+            .unwrap();
+        match preset {
+            Preset::ScreenShare => {
+                assert_eq!(param.iUsageType, SCREEN_CONTENT_REAL_TIME);
+                assert_eq!(param.iRCMode, RC_TIMESTAMP_MODE);
+                assert!(param.bEnableBackgroundDetection && !param.bEnableAdaptiveQuant);
+            }
+            Preset::VideoCall => {
+                assert_eq!(param.iTemporalLayerNum, 2);
+                assert!(param.bEnableDenoise && param.bEnableFrameSkip);
+            }
+            Preset::Archive => {
+                assert_eq!(param.iUsageType, CAMERA_VIDEO_NON_REAL_TIME);
+                assert_eq!(param.iEntropyCodingModeFlag, 1);
+                assert_eq!(param.sSpatialLayers[0].uiProfileIdc, PRO_MAIN);
+                assert_eq!(param.uiIntraPeriod, 60);
+            }
+            Preset::LowLatencyGame => {
+                assert_eq!(param.iComplexityMode, LOW_COMPLEXITY);
+                assert_eq!(param.iMaxBitrate, param.iTargetBitrate);
+                assert!(!param.bEnableFrameSkip && !param.bEnableSceneChangeDetect);
+            }
+        }
+        encoder.initialize(&param).unwrap();
+
+        let pattern = match preset {
+            Preset::ScreenShare => Pattern::ScrollingText,
+            _ => Pattern::Gradient,
+        };
+        let source = TestSource::new(pattern, width, height);
+        let mut decoder = Decoder::new().unwrap();
+        decoder.initialize(&SDecodingParam::default()).unwrap();
+        let mut idrs = 0;
+        for index in 0..90 {
+            let frame = source.frame(index);
+            let encoded = encoder.encode(&frame.as_yuv().unwrap()).unwrap();
+            if encoded.is_idr() {
+                idrs += 1;
+            }
+            if preset == Preset::LowLatencyGame && index == 0 {
+                let slices: usize = encoded
+                    .layers
+                    .iter()
+                    .filter(|layer| layer.video_coding)
+                    .map(|layer| layer.nals.len())
+                    .sum();
+                assert_eq!(slices, 4);
+            }
+            if encoded.size() == 0 {
+                continue;
+            }
+            let decoded = decoder.decode(&encoded.to_bytes()).unwrap().unwrap();
+            let quality = metrics::compare(&frame, &decoded).unwrap();
+            assert!(quality.psnr.y > 28.0, "{:?}: {:?}", preset, quality);
+        }
+        let expected_idrs = if preset == Preset::Archive { 2 } else { 1 };
+        assert_eq!(idrs, expected_idrs, "{:?}", preset);
+    }
+
+    let encoder = Encoder::new().unwrap();
+    assert!(Preset::VideoCall
+        .params(&encoder, 320, 192, 0.0, 400_000)
+        .is_err());
+    assert!(Preset::Archive.params(&encoder, 320, 192, 30.0, 0).is_err());
+}
+
+#[test]
+fn sps_vui_parsing() {
+    use vui::sps::read_sps_vui;
+    use vui::{ColorDescription, VideoFormat, Vui};
+
+    // Baseline 64x64 SPS without VUI: 67 42 c0 1e, then sps_id 0, log2_max_frame_num 4,
+    // POC type 2, one reference frame, 4x4 macroblocks, frame_mbs_only, direct_8x8.
+    let sps = [0, 0, 0, 1, 0x67, 0x42, 0xc0, 0x1e, 0xda, 0x10, 0x99];
+    assert_eq!(read_sps_vui(&sps).unwrap(), None);
+
+    // The same SPS with a VUI of aspect_ratio_idc 1, video_format 0 and BT.709 colors.
+    let hd = [
+        0, 0, 0, 1, 0x67, 0x42, 0xc0, 0x1e, 0xda, 0x10, 0x9b, 0x01, 0x42, 0x02, 0x02, 0x02, 0x04,
+    ];
+    let expected = Vui::new()
+        .sample_aspect_ratio(1, 1)
+        .video_format(VideoFormat::Component)
+        .color(ColorDescription::BT709);
+    assert_eq!(read_sps_vui(&hd).unwrap(), Some(expected));
+
+    // An explicit 59:54 ratio, full range and sRGB.
+    let srgb = [
+        0, 0, 0, 1, 0x67, 0x42, 0xc0, 0x1e, 0xda, 0x10, 0x9b, 0xff, 0x00, 0x3b, 0x00, 0x36, 0x6e,
+        0x02, 0x1a, 0x0c, 0x04,
+    ];
+    let expected = Vui::new()
+        .sample_aspect_ratio(59, 54)
+        .full_range(true)
+        .color(ColorDescription::SRGB);
+    assert_eq!(read_sps_vui(&srgb).unwrap(), Some(expected));
+    assert!(read_sps_vui(&[0, 0, 1, 0x68, 0xce]).is_err());
+}
+
+#[test]
+fn encoder_vui() {
+    use vui::sps::read_sps_vui;
+    use vui::{ColorDescription, Vui};
+
+    let vui = Vui::new()
+        .sample_aspect_ratio(4, 3)
+        .full_range(true)
+        .color(ColorDescription::BT2020);
+    let layout = svc::SvcLayout::new(vec![
+        svc::SpatialLayer::new(64, 64, 30.0, 100_000),
+        svc::SpatialLayer::new(128, 128, 30.0, 300_000),
+    ]);
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, 128, 128);
+    layout.apply(&mut param).unwrap();
+    assert!(Vui::new()
+        .sample_aspect_ratio(0, 1)
+        .apply(&mut param)
+        .is_err());
+    vui.apply(&mut param).unwrap();
+    assert_eq!(param.sSpatialLayers[1].eAspectRatio, ASP_EXT_SAR);
+    assert_eq!(Vui::from_params(&param), vui);
+    encoder.initialize(&param).unwrap();
+    assert_eq!(Vui::from_params(&encoder.params().unwrap()), vui);
+
+    let sets = encoder.parameter_sets().unwrap();
+    assert_eq!(sets.sps.len(), 2);
+    for (_, sps) in &sets.sps {
+        assert_eq!(read_sps_vui(sps).unwrap(), Some(vui));
+    }
+
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    let source = testsrc::TestSource::new(testsrc::Pattern::ColorBars, 128, 128).frame(0);
+    let frame = encoder.encode(&source.as_yuv().unwrap()).unwrap();
+    let decoded = decoder.decode(&frame.to_bytes()).unwrap().unwrap();
+    assert_eq!((decoded.width, decoded.height), (128, 128));
+    let sar = decoder.get_option(option::decoder::GET_SAR_INFO).unwrap();
+    assert_eq!((sar.uiSarWidth, sar.uiSarHeight), (4, 3));
+}
+
+#[test]
+fn timestamp_conversion() {
+    use std::time::Duration;
+    use timestamp::{Timebase, Timeline};
+
+    assert_eq!(
+        Timebase::MPEG.to_duration(135_000).unwrap(),
+        Duration::from_millis(1500)
+    );
+    assert_eq!(
+        Timebase::new(1001, 30_000).unwrap().to_duration(3).unwrap(),
+        Duration::from_nanos(100_100_000)
+    );
+    assert!(Timebase::new(1, 0).is_err());
+    assert!(Timebase::new(u32::MAX, 1)
+        .unwrap()
+        .to_duration(u64::MAX)
+        .is_err());
+
+    let range = 1u64 << 33;
+    let mut timeline = Timeline::wrapping(Timebase::MPEG, 33).unwrap();
+    let before_wrap = timeline.duration(range - 9000).unwrap();
+    let after_wrap = timeline.duration(9000).unwrap();
+    assert_eq!(after_wrap - before_wrap, Duration::from_millis(200));
+    // A late timestamp from before the wrap lands between the two.
+    let late = timeline.duration(range - 4500).unwrap();
+    assert_eq!(late - before_wrap, Duration::from_millis(50));
+    assert_eq!(
+        timeline.duration(18_000).unwrap() - after_wrap,
+        Duration::from_millis(100)
+    );
+    assert!(timeline.duration(range).is_err());
+    assert!(Timeline::wrapping(Timebase::MPEG, 64).is_err());
+}
+
+#[test]
+fn timestamp_rate_control() {
+    use rate_control::RateControl;
+    use testsrc::{Pattern, TestSource};
+    use timestamp::{Timebase, Timeline};
+
+    let (width, height, bitrate) = (176, 144, 150_000);
+    let mut encoder = Encoder::new().unwrap();
+    let mut param = camera_param(&encoder, width, height);
+    param.iUsageType = SCREEN_CONTENT_REAL_TIME;
+    RateControl::Timestamp {
+        bitrate,
+        max_bitrate: None,
+        frame_skip: true,
+    }
+    .apply(&mut param)
+    .unwrap();
+    encoder.initialize(&param).unwrap();
+
+    // Screen capture only delivers a frame when something changed: bursts of 20 ms
+    // frames with pauses of up to 300 ms, on an RTP clock about to wrap.
+    let source = TestSource::new(Pattern::ScrollingText, width, height);
+    let mut timeline = Timeline::wrapping(Timebase::MPEG, 32).unwrap();
+    let mut pts = (1u64 << 32) - 90_000;
+    let start = timeline.duration(pts).unwrap();
+    let mut bytes = 0;
+    let mut last = start;
+    for index in 0..100 {
+        let gap = if index % 10 == 9 { 300 } else { 20 };
+        pts = (pts + gap * 90) & 0xffff_ffff;
+        let time = timeline.duration(pts).unwrap();
+        let frame = source.frame(index);
+        let encoded = encoder.encode_at(&frame.as_yuv().unwrap(), time).unwrap();
+        assert_eq!(encoded.timestamp, time.as_millis() as i64);
+        bytes += encoded.size();
+        last = time;
+    }
+    let seconds = (last - start).as_secs_f64();
+    let achieved = bytes as f64 * 8.0 / seconds;
+    assert!(
+        achieved > 0.5 * f64::from(bitrate) && achieved < 1.5 * f64::from(bitrate),
+        "reached {:.0} bps",
+        achieved
+    );
+
+    let frame = source.frame(100);
+    let repeated = encoder.encode_at(&frame.as_yuv().unwrap(), last);
+    assert_eq!(
+        repeated.unwrap_err(),
+        Error::NonMonotonicTimestamp {
+            previous: last.as_millis() as i64,
+            current: last.as_millis() as i64,
+        }
+    );
+    let next = last + Duration::from_millis(20);
+    assert!(encoder.encode_at(&frame.as_yuv().unwrap(), next).is_ok());
+}
+
+#[test]
+fn yuv_frame_validation() {
+    use yuv::YuvFrameRef;
+
+    // 6x4 picture in a buffer with 8-byte luma and 4-byte chroma rows.
+    let y = [0u8; 8 * 4];
+    let uv = [128u8; 4 * 2];
+    let frame = YuvFrameRef::new(6, 4, [&y, &uv, &uv], [8, 4, 4]).unwrap();
+    assert_eq!((frame.width(), frame.height()), (6, 4));
+    let pic = frame.picture();
+    assert_eq!(pic.iStride, [8, 4, 4, 0]);
+    assert_eq!(pic.pData[0] as *const u8, y.as_ptr());
+
+    assert!(YuvFrameRef::new(5, 4, [&y, &uv, &uv], [8, 4, 4]).is_err());
+    assert!(YuvFrameRef::new(6, 3, [&y, &uv, &uv], [8, 4, 4]).is_err());
+    assert!(YuvFrameRef::new(0, 4, [&y, &uv, &uv], [8, 4, 4]).is_err());
+    assert!(YuvFrameRef::new(6, 4, [&y, &uv, &uv], [5, 4, 4]).is_err());
+    assert!(YuvFrameRef::new(6, 4, [&y, &uv, &uv], [8, 2, 4]).is_err());
+    assert!(YuvFrameRef::new(6, 4, [&y[1..], &uv, &uv], [8, 4, 4]).is_err());
+    assert!(YuvFrameRef::new(6, 4, [&y, &uv, &uv[1..]], [8, 4, 4]).is_err());
+    assert!(YuvFrameRef::new(6, 4, [&y, &uv, &uv], [usize::MAX, 4, 4]).is_err());
+
+    let packed = [7u8; 6 * 4 * 3 / 2];
+    let frame = YuvFrameRef::packed(6, 4, &packed).unwrap();
+    assert_eq!(frame.strides(), [6, 3, 3]);
+    assert_eq!(frame.planes()[2].len(), 6);
+    assert!(YuvFrameRef::packed(6, 4, &packed[1..]).is_err());
+    assert!(YuvFrameRef::packed(5, 4, &packed).is_err());
+    let huge = c_int::MAX as usize - 1;
+    assert!(YuvFrameRef::packed(huge, huge, &packed).is_err());
+
+    let mut owned = Frame::new(6, 4, 40);
+    assert_eq!(owned.as_yuv().unwrap().timestamp, 40);
+    owned.planes[1].pop();
+    assert!(owned.as_yuv().is_err());
+    assert!(Frame::new(33, 17, 0).as_yuv().is_err());
+}
+
+#[test]
+fn encode_from_strided_buffer() {
+    use testsrc::{Pattern, TestSource};
+    use yuv::YuvFrameRef;
+
+    let (width, height, stride) = (64, 48, 80);
+    let source = TestSource::new(Pattern::ColorBars, width, height).frame(0);
+    // Copy the picture into rows padded the way capture drivers often align them.
+    let mut planes = [
+        vec![0; stride * height],
+        vec![0; stride / 2 * height / 2],
+        vec![0; stride / 2 * height / 2],
+    ];
+    let rows = [(width, stride), (width / 2, stride / 2), (width / 2, stride / 2)];
+    for ((padded, packed), &(row, padded_row)) in
+        planes.iter_mut().zip(&source.planes).zip(&rows)
+    {
+        for (padded, packed) in padded.chunks_mut(padded_row).zip(packed.chunks(row)) {
+            padded[..row].copy_from_slice(packed);
+        }
+    }
+
+    let mut encoder = Encoder::new().unwrap();
+    let param = camera_param(&encoder, width, height);
+    encoder.initialize(&param).unwrap();
+    let frame = YuvFrameRef::new(
+        width,
+        height,
+        [&planes[0], &planes[1], &planes[2]],
+        [stride, stride / 2, stride / 2],
+    )
+    .unwrap();
+    let encoded = encoder.encode(&frame).unwrap();
+
+    let mut decoder = Decoder::new().unwrap();
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+    let decoded = decoder.decode(&encoded.to_bytes()).unwrap().unwrap();
+    let quality = metrics::compare(&source, &decoded).unwrap();
+    assert!(quality.psnr.y > 30.0, "{:?}", quality);
+}
+
+#[test]
+fn frame_pool_recycles() {
+    use frame_pool::FramePool;
+
+    // 6x4 decoder output with 8-byte luma and 4-byte chroma rows.
+    let y: Vec<u8> = (0..32).collect();
+    let u = [1u8; 8];
+    let v = [2u8; 8];
+    let decoded = DecodedFrame {
+        state: dsErrorFree,
+        width: 6,
+        height: 4,
+        timestamp: 99,
+        y: &y,
+        u: &u,
+        v: &v,
+        y_stride: 8,
+        uv_stride: 4,
+    };
+
+    assert!(FramePool::new(24, 0).is_err());
+    let pool = FramePool::new(32, 16).unwrap();
+    let frame = pool.copy(&decoded);
+    assert_eq!((frame.width(), frame.height(), frame.timestamp()), (6, 4, 99));
+    assert_eq!(frame.strides(), [32, 32, 32]);
+    for plane in &[frame.y(), frame.u(), frame.v()] {
+        assert_eq!(plane.as_ptr() as usize % 32, 0);
+    }
+    assert_eq!(&frame.y()[32..38], &y[8..14]);
+    assert_eq!(&frame.v()[32..35], &[2, 2, 2]);
+    let yuv = frame.as_yuv().unwrap();
+    assert_eq!((yuv.timestamp, yuv.strides()), (99, [32, 32, 32]));
+
+    let shared = frame.clone();
+    let luma = frame.y().as_ptr();
+    let data = frame.data_ptr();
+    drop(frame);
+    assert_eq!(pool.available(), 0);
+    drop(shared);
+    assert_eq!(pool.available(), 1);
+
+    let reused = pool.copy(&decoded);
+    assert_eq!(reused.y().as_ptr(), luma);
+    assert_eq!(reused.data_ptr(), data);
+    assert_eq!(pool.available(), 0);
+    let second = pool.copy(&decoded);
+    assert_ne!(second.data_ptr(), data);
+    drop(second);
+    assert_eq!(pool.available(), 1);
+    drop(pool);
+    drop(reused);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn rgb_image_conversion() {
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    let mut image = RgbImage::from_pixel(4, 2, Rgb([255, 255, 255]));
+    for (x, y) in &[(2, 0), (3, 0), (2, 1), (3, 1)] {
+        image.put_pixel(*x, *y, Rgb([200, 30, 60]));
+    }
+    let frame = Frame::from_image(&DynamicImage::ImageRgb8(image.clone()), 5);
+    assert_eq!(frame.timestamp, 5);
+    assert_eq!(&frame.planes[0][..2], &[235, 235]);
+    assert_eq!((frame.planes[1][0], frame.planes[2][0]), (128, 128));
+
+    let rgb = frame.to_rgb_image();
+    assert_eq!(rgb.dimensions(), (4, 2));
+    for y in 0..2 {
+        for x in 0..4 {
+            let (expected, actual) = (image.get_pixel(x, y).0, rgb.get_pixel(x, y).0);
+            for (expected, actual) in expected.iter().zip(&actual) {
+                assert!((i32::from(*expected) - i32::from(*actual)).abs() <= 3);
+            }
+        }
+    }
+
+    // 75% white bar from `testsrc`, read through a padded decoder-style layout.
+    let planes = [vec![180u8; 8 * 2], vec![128u8; 4], vec![128u8; 4]];
+    let decoded = DecodedFrame {
+        state: dsErrorFree,
+        width: 2,
+        height: 2,
+        timestamp: 0,
+        y: &planes[0],
+        u: &planes[1],
+        v: &planes[2],
+        y_stride: 8,
+        uv_stride: 4,
+    };
+    assert_eq!(decoded.to_rgb_image().get_pixel(1, 1).0, [191, 191, 191]);
+}
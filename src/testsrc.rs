@@ -0,0 +1,155 @@
+//! Reproducible synthetic I420 content for tests, benchmarks and examples.
+//!
+//! Every frame is a pure function of the pattern, the frame size and the frame index, so
+//! the same source produces the same bytes on every run and every machine. All patterns
+//! move, so P frames have real motion to search instead of being skipped.
+
+use frame::Frame;
+
+/// 75% color bars in BT.601 limited range: white, yellow, cyan, green, magenta, red, blue.
+const BARS: [(u8, u8, u8); 7] = [
+    (180, 128, 128),
+    (162, 44, 142),
+    (131, 156, 44),
+    (112, 72, 58),
+    (84, 184, 198),
+    (65, 100, 212),
+    (35, 212, 114),
+];
+
+const GLYPH_WIDTH: usize = 8;
+const GLYPH_HEIGHT: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Vertical color bars sliding right by 2 pixels per frame.
+    ColorBars,
+    /// Diagonal luma and chroma gradients scrolling by 2 pixels per frame.
+    Gradient,
+    /// Independent pseudo-random noise in every frame; the worst case for prediction.
+    Noise { seed: u32 },
+    /// Dark glyph-like blocks on a light background scrolling up by 1 pixel per frame, in
+    /// the manner of a terminal or document.
+    ScrollingText,
+    /// Switches between the other patterns every `interval` frames, so every switch is a
+    /// scene cut.
+    SceneCuts { interval: usize },
+}
+
+/// Generates frames of one pattern and size. Iterating yields frames `0, 1, ...` with
+/// timestamps following `frame_rate`.
+#[derive(Debug, Clone)]
+pub struct TestSource {
+    pub pattern: Pattern,
+    pub width: usize,
+    pub height: usize,
+    pub frame_rate: f64,
+    next: usize,
+}
+
+/// Bit mixer from "Hash Prospector" (lowbias32), good enough to make noise look random.
+fn hash(mut value: u32) -> u32 {
+    value ^= value >> 16;
+    value = value.wrapping_mul(0x7feb_352d);
+    value ^= value >> 15;
+    value = value.wrapping_mul(0x846c_a68b);
+    value ^= value >> 16;
+    value
+}
+
+fn hash3(a: usize, b: usize, c: usize) -> u32 {
+    hash(a as u32 ^ hash(b as u32 ^ hash(c as u32)))
+}
+
+impl TestSource {
+    pub fn new(pattern: Pattern, width: usize, height: usize) -> Self {
+        TestSource {
+            pattern,
+            width,
+            height,
+            frame_rate: 30.0,
+            next: 0,
+        }
+    }
+
+    /// Frame `index` of the sequence.
+    pub fn frame(&self, index: usize) -> Frame {
+        let timestamp = (index as f64 * 1000.0 / self.frame_rate) as i64;
+        let mut frame = Frame::new(self.width, self.height, timestamp);
+        self.draw(self.pattern, index, &mut frame);
+        frame
+    }
+
+    fn draw(&self, pattern: Pattern, index: usize, frame: &mut Frame) {
+        match pattern {
+            Pattern::ColorBars => self.fill(frame, |x, _| {
+                let bar_width = (self.width / BARS.len()).max(1);
+                BARS[((x + self.width - (2 * index) % self.width) / bar_width) % BARS.len()]
+            }),
+            Pattern::Gradient => self.fill(frame, |x, y| {
+                let shift = 2 * index;
+                (
+                    ((x + y + shift) % 220 + 16) as u8,
+                    ((x + shift) % 224 + 16) as u8,
+                    ((y + shift) % 224 + 16) as u8,
+                )
+            }),
+            Pattern::Noise { seed } => self.fill(frame, |x, y| {
+                let noise = hash3(x, y, index ^ ((seed as usize) << 20));
+                (noise as u8, (noise >> 8) as u8, (noise >> 16) as u8)
+            }),
+            Pattern::ScrollingText => self.fill(frame, |x, y| {
+                let line = (y + index) / GLYPH_HEIGHT;
+                let row_in_line = (y + index) % GLYPH_HEIGHT;
+                let column = x / GLYPH_WIDTH;
+                let glyph = hash3(column, line, 0x7e57);
+                // Blank glyphs between words, and two blank rows between lines.
+                let inked = glyph & 0xf0 != 0
+                    && row_in_line < GLYPH_HEIGHT - 2
+                    && (glyph >> (8 + (row_in_line % 4) * 4 + (x % GLYPH_WIDTH) / 2)) & 1 != 0;
+                if inked {
+                    (30, 128, 128)
+                } else {
+                    (230, 128, 128)
+                }
+            }),
+            Pattern::SceneCuts { interval } => {
+                let scene = index / interval.max(1);
+                let pattern = match scene % 4 {
+                    0 => Pattern::ColorBars,
+                    1 => Pattern::Gradient,
+                    2 => Pattern::ScrollingText,
+                    _ => Pattern::Noise { seed: scene as u32 },
+                };
+                self.draw(pattern, index, frame)
+            }
+        }
+    }
+
+    /// Sets every pixel to `color(x, y)`, sampling chroma at the top-left luma position
+    /// of each 2x2 block.
+    fn fill<F: Fn(usize, usize) -> (u8, u8, u8)>(&self, frame: &mut Frame, color: F) {
+        let chroma_width = self.width.div_ceil(2);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (luma, u, v) = color(x, y);
+                frame.planes[0][y * self.width + x] = luma;
+                if x % 2 == 0 && y % 2 == 0 {
+                    let offset = (y / 2) * chroma_width + x / 2;
+                    frame.planes[1][offset] = u;
+                    frame.planes[2][offset] = v;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for TestSource {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        let frame = self.frame(self.next);
+        self.next += 1;
+        Some(frame)
+    }
+}
@@ -0,0 +1,79 @@
+//! Encoder threading (`iMultipleThreadIdc`) together with the slice layout that makes it
+//! pay off.
+//!
+//! openh264 splits the work inside a frame by slice, so with one slice per spatial layer
+//! extra threads only help when there are several layers. `EncoderThreads::apply`
+//! therefore gives every layer in `SM_SINGLE_SLICE` or `SM_FIXEDSLCNUM_SLICE` mode one
+//! slice per thread. Raster and size-limited slice modes are explicit layouts and stay as
+//! configured.
+//!
+//! The openh264 1.7 this crate builds against always decodes on the calling thread;
+//! `DECODER_OPTION_NUM_OF_THREADS` only exists in later releases.
+
+use std::os::raw::{c_int, c_uint, c_ushort};
+
+use error::{Error, Result};
+use {SEncParamExt, MAX_SLICES_NUM_TMP, MAX_THREADS_NUM, SM_FIXEDSLCNUM_SLICE, SM_SINGLE_SLICE};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderThreads {
+    /// `0..=MAX_THREADS_NUM`. 0 lets openh264 pick from the CPU count, 1 disables
+    /// threading.
+    pub threads: u16,
+    /// `bUseLoadBalancing`: move slice boundaries between frames so that threads finish
+    /// at the same time.
+    pub load_balancing: bool,
+}
+
+impl EncoderThreads {
+    pub fn new(threads: u16) -> Self {
+        EncoderThreads {
+            threads,
+            load_balancing: true,
+        }
+    }
+
+    /// Slice count for a layer `height` pixels high: one per thread, but no more than
+    /// there are macroblock rows or openh264 allows. 0 asks openh264 to match the CPU
+    /// count.
+    fn slice_count(&self, height: c_int) -> c_uint {
+        if self.threads == 0 {
+            return 0;
+        }
+        let mb_rows = ((height.max(1) + 15) / 16) as c_uint;
+        c_uint::from(self.threads)
+            .min(mb_rows)
+            .min(MAX_SLICES_NUM_TMP as c_uint)
+    }
+
+    /// Writes the thread count into `param` and aligns the slice count of each configured
+    /// spatial layer to it. Call after the layer sizes are set, e.g. by
+    /// `SvcLayout::apply`.
+    pub fn apply(&self, param: &mut SEncParamExt) -> Result<()> {
+        if u32::from(self.threads) > MAX_THREADS_NUM {
+            return Err(Error::InvalidArgument(format!(
+                "Thread count {} is out of range 0..={}",
+                self.threads, MAX_THREADS_NUM
+            )));
+        }
+        param.iMultipleThreadIdc = self.threads as c_ushort;
+        param.bUseLoadBalancing = self.load_balancing;
+        if self.threads == 1 {
+            return Ok(());
+        }
+        let layers = (param.iSpatialLayerNum.max(0) as usize).min(param.sSpatialLayers.len());
+        for layer in &mut param.sSpatialLayers[..layers] {
+            let slices = &mut layer.sSliceArgument;
+            if slices.uiSliceMode != SM_SINGLE_SLICE && slices.uiSliceMode != SM_FIXEDSLCNUM_SLICE {
+                continue;
+            }
+            slices.uiSliceNum = self.slice_count(layer.iVideoHeight);
+            slices.uiSliceMode = if slices.uiSliceNum == 1 {
+                SM_SINGLE_SLICE
+            } else {
+                SM_FIXEDSLCNUM_SLICE
+            };
+        }
+        Ok(())
+    }
+}
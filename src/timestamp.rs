@@ -0,0 +1,133 @@
+//! Capture timestamps for `Encoder::encode_at`.
+//!
+//! openh264 takes millisecond timestamps, which `RC_TIMESTAMP_MODE` paces the bitrate by.
+//! Capture APIs and containers usually count in ticks of a rational timebase instead,
+//! e.g. 1/90000 s for MPEG-TS, and their counters wrap. `Timeline` turns such presentation
+//! timestamps into a `Duration` since the start of the counter, undoing the wraps.
+
+use std::time::Duration;
+
+use error::{Error, Result};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Length of one tick, `num / den` seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timebase {
+    num: u32,
+    den: u32,
+}
+
+/// Converts presentation timestamps of one stream, keeping track of counter wraps.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    timebase: Timebase,
+    /// `1 << bits` for a counter of `bits` bits.
+    range: Option<u64>,
+    last: Option<u64>,
+    /// Ticks added by the wraps seen so far.
+    offset: u64,
+}
+
+impl Timebase {
+    pub const MILLISECONDS: Timebase = Timebase { num: 1, den: 1000 };
+    /// The 90 kHz clock of RTP video and MPEG-TS.
+    pub const MPEG: Timebase = Timebase {
+        num: 1,
+        den: 90_000,
+    };
+
+    pub fn new(num: u32, den: u32) -> Result<Timebase> {
+        if num == 0 || den == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "Timebase {}/{} isn't positive",
+                num, den
+            )));
+        }
+        Ok(Timebase { num, den })
+    }
+
+    pub fn num(&self) -> u32 {
+        self.num
+    }
+
+    pub fn den(&self) -> u32 {
+        self.den
+    }
+
+    /// Time of `ticks` ticks, rounded down to the nanosecond.
+    pub fn to_duration(&self, ticks: u64) -> Result<Duration> {
+        let total = u128::from(ticks) * u128::from(self.num);
+        let den = u128::from(self.den);
+        let secs = total / den;
+        if secs > u128::from(u64::MAX) {
+            return Err(Error::InvalidArgument(format!(
+                "{} ticks of {}/{} s don't fit a Duration",
+                ticks, self.num, self.den
+            )));
+        }
+        let nanos = (total % den) * NANOS_PER_SEC / den;
+        Ok(Duration::new(secs as u64, nanos as u32))
+    }
+}
+
+impl Timeline {
+    /// A timeline for a counter that doesn't wrap.
+    pub fn new(timebase: Timebase) -> Timeline {
+        Timeline {
+            timebase,
+            range: None,
+            last: None,
+            offset: 0,
+        }
+    }
+
+    /// A timeline for a counter of `bits` bits, e.g. 33 for MPEG-TS or 32 for RTP.
+    pub fn wrapping(timebase: Timebase, bits: u32) -> Result<Timeline> {
+        if bits == 0 || bits > 63 {
+            return Err(Error::InvalidArgument(format!(
+                "Counter width {} is out of range 1..=63",
+                bits
+            )));
+        }
+        Ok(Timeline {
+            range: Some(1 << bits),
+            ..Timeline::new(timebase)
+        })
+    }
+
+    pub fn timebase(&self) -> Timebase {
+        self.timebase
+    }
+
+    /// Time of `pts` since the counter started. A jump of more than half the counter range
+    /// is taken for a wrap: backwards past the end of the range, or forwards for a late
+    /// timestamp from before the last wrap.
+    pub fn duration(&mut self, pts: u64) -> Result<Duration> {
+        let mut offset = self.offset;
+        if let Some(range) = self.range {
+            if pts >= range {
+                return Err(Error::InvalidArgument(format!(
+                    "Timestamp {} doesn't fit a {}-bit counter",
+                    pts,
+                    range.trailing_zeros()
+                )));
+            }
+            if let Some(last) = self.last {
+                if last > pts && last - pts > range >> 1 {
+                    offset += range;
+                } else if pts > last && pts - last > range >> 1 && offset >= range {
+                    // Reordered from before the last wrap; doesn't move the timeline.
+                    return self.timebase.to_duration(offset - range + pts);
+                }
+            }
+        }
+        let ticks = offset.checked_add(pts).ok_or_else(|| {
+            Error::InvalidArgument(format!("Timestamp {} overflows the timeline", pts))
+        })?;
+        let duration = self.timebase.to_duration(ticks)?;
+        self.offset = offset;
+        self.last = Some(pts);
+        Ok(duration)
+    }
+}
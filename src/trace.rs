@@ -0,0 +1,82 @@
+//! Forwards openh264's trace output to the `log` crate.
+//!
+//! Every `Encoder` and `Decoder` installs `trace_callback` on creation, with a boxed
+//! `TraceContext` as the callback context. Records use the target
+//! `openh264::encoder#<n>`/`openh264::decoder#<n>`, where `n` numbers instances in creation
+//! order, so output from concurrent streams can be told apart and filtered by prefix.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::{Level, LevelFilter};
+
+use {
+    WELS_LOG_DEBUG, WELS_LOG_DETAIL, WELS_LOG_ERROR, WELS_LOG_INFO, WELS_LOG_QUIET,
+    WELS_LOG_WARNING,
+};
+
+static NEXT_INSTANCE_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) struct TraceContext {
+    pub target: String,
+}
+
+impl TraceContext {
+    pub fn new(kind: &str) -> Box<TraceContext> {
+        let id = NEXT_INSTANCE_ID.fetch_add(1, Ordering::Relaxed);
+        Box::new(TraceContext {
+            target: format!("openh264::{}#{}", kind, id),
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self as *const TraceContext as *mut c_void
+    }
+}
+
+/// The most verbose `WELS_LOG_*` level the current `log::max_level()` lets through, so
+/// openh264 doesn't format messages that would be thrown away.
+pub(crate) fn wels_log_level() -> c_int {
+    (match log::max_level() {
+        LevelFilter::Off => WELS_LOG_QUIET,
+        LevelFilter::Error => WELS_LOG_ERROR,
+        LevelFilter::Warn => WELS_LOG_WARNING,
+        LevelFilter::Info => WELS_LOG_INFO,
+        LevelFilter::Debug => WELS_LOG_DEBUG,
+        LevelFilter::Trace => WELS_LOG_DETAIL,
+    }) as c_int
+}
+
+fn to_log_level(level: c_int) -> Level {
+    match level as u32 {
+        WELS_LOG_ERROR => Level::Error,
+        WELS_LOG_WARNING => Level::Warn,
+        WELS_LOG_INFO => Level::Info,
+        WELS_LOG_DEBUG => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+pub(crate) unsafe extern "C" fn trace_callback(
+    context: *mut c_void,
+    level: c_int,
+    message: *const c_char,
+) {
+    // Unwinding into openh264's C++ frames is undefined behavior, so a panicking logger
+    // loses the record instead.
+    let _ = catch_unwind(AssertUnwindSafe(|| {
+        if context.is_null() || message.is_null() {
+            return;
+        }
+        let context = &*(context as *const TraceContext);
+        let message = CStr::from_ptr(message).to_string_lossy();
+        log!(
+            target: &context.target,
+            to_log_level(level),
+            "{}",
+            message.trim_end()
+        );
+    }));
+}
@@ -0,0 +1,532 @@
+//! Video usability information (VUI) of the SPS: sample aspect ratio, signal range and
+//! color description.
+//!
+//! openh264 writes the VUI of each SPS and subset SPS from the `SSpatialLayerConfig` of its
+//! spatial layer: `bAspectRatioPresent`, `eAspectRatio` and `sAspectRatioExt*` for the
+//! sample aspect ratio, `bVideoSignalTypePresent` and the fields after it for the video
+//! signal type. `Vui::apply` fills them in before the encoder is initialized.
+
+use error::{Error, Result};
+use {ASP_160x99, ESampleAspectRatio, SEncParamExt, SSpatialLayerConfig, ASP_EXT_SAR};
+
+/// Shape of one sample, `width:height`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleAspectRatio {
+    pub width: u16,
+    pub height: u16,
+}
+
+/// `video_format`: what the source was before it was digitized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    Component,
+    Pal,
+    Ntsc,
+    Secam,
+    Mac,
+    Unspecified,
+}
+
+/// `colour_primaries`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorPrimaries {
+    Bt709,
+    Unspecified,
+    /// SMPTE 170M, the 525-line BT.601 primaries.
+    Bt601,
+    Bt2020,
+    Other(u8),
+}
+
+/// `transfer_characteristics`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferCharacteristics {
+    Bt709,
+    Unspecified,
+    Bt601,
+    /// IEC 61966-2-1.
+    Srgb,
+    Bt2020,
+    Other(u8),
+}
+
+/// `matrix_coefficients`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatrixCoefficients {
+    Bt709,
+    Unspecified,
+    Bt601,
+    /// BT.2020 non-constant luminance.
+    Bt2020,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorDescription {
+    pub primaries: ColorPrimaries,
+    pub transfer: TransferCharacteristics,
+    pub matrix: MatrixCoefficients,
+}
+
+/// `video_signal_type_present_flag` and the fields it guards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VideoSignal {
+    pub format: VideoFormat,
+    /// Samples use the whole 0..=255 range instead of 16..=235 (luma) and 16..=240
+    /// (chroma).
+    pub full_range: bool,
+    pub color: Option<ColorDescription>,
+}
+
+/// The VUI fields this crate sets. `None` leaves a field out of the SPS, which means
+/// unspecified.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Vui {
+    pub sample_aspect_ratio: Option<SampleAspectRatio>,
+    pub video_signal: Option<VideoSignal>,
+}
+
+/// `aspect_ratio_idc` 1 to 16 of Table E-1; 255 signals an explicit ratio.
+/// `ESampleAspectRatio` stops at `ASP_160x99`, so the last three go out as explicit ratios.
+const ASPECT_RATIOS: [(u16, u16); 16] = [
+    (1, 1),
+    (12, 11),
+    (10, 11),
+    (16, 11),
+    (40, 33),
+    (24, 11),
+    (20, 11),
+    (32, 11),
+    (80, 33),
+    (18, 11),
+    (15, 11),
+    (64, 33),
+    (160, 99),
+    (4, 3),
+    (3, 2),
+    (2, 1),
+];
+
+impl VideoFormat {
+    fn code(self) -> u8 {
+        match self {
+            VideoFormat::Component => 0,
+            VideoFormat::Pal => 1,
+            VideoFormat::Ntsc => 2,
+            VideoFormat::Secam => 3,
+            VideoFormat::Mac => 4,
+            VideoFormat::Unspecified => 5,
+        }
+    }
+
+    fn from_code(code: u8) -> Self {
+        match code {
+            0 => VideoFormat::Component,
+            1 => VideoFormat::Pal,
+            2 => VideoFormat::Ntsc,
+            3 => VideoFormat::Secam,
+            4 => VideoFormat::Mac,
+            _ => VideoFormat::Unspecified,
+        }
+    }
+}
+
+impl ColorPrimaries {
+    pub fn code(self) -> u8 {
+        match self {
+            ColorPrimaries::Bt709 => 1,
+            ColorPrimaries::Unspecified => 2,
+            ColorPrimaries::Bt601 => 6,
+            ColorPrimaries::Bt2020 => 9,
+            ColorPrimaries::Other(code) => code,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            1 => ColorPrimaries::Bt709,
+            2 => ColorPrimaries::Unspecified,
+            6 => ColorPrimaries::Bt601,
+            9 => ColorPrimaries::Bt2020,
+            code => ColorPrimaries::Other(code),
+        }
+    }
+}
+
+impl TransferCharacteristics {
+    pub fn code(self) -> u8 {
+        match self {
+            TransferCharacteristics::Bt709 => 1,
+            TransferCharacteristics::Unspecified => 2,
+            TransferCharacteristics::Bt601 => 6,
+            TransferCharacteristics::Srgb => 13,
+            TransferCharacteristics::Bt2020 => 14,
+            TransferCharacteristics::Other(code) => code,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            1 => TransferCharacteristics::Bt709,
+            2 => TransferCharacteristics::Unspecified,
+            6 => TransferCharacteristics::Bt601,
+            13 => TransferCharacteristics::Srgb,
+            14 => TransferCharacteristics::Bt2020,
+            code => TransferCharacteristics::Other(code),
+        }
+    }
+}
+
+impl MatrixCoefficients {
+    pub fn code(self) -> u8 {
+        match self {
+            MatrixCoefficients::Bt709 => 1,
+            MatrixCoefficients::Unspecified => 2,
+            MatrixCoefficients::Bt601 => 6,
+            MatrixCoefficients::Bt2020 => 9,
+            MatrixCoefficients::Other(code) => code,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        match code {
+            1 => MatrixCoefficients::Bt709,
+            2 => MatrixCoefficients::Unspecified,
+            6 => MatrixCoefficients::Bt601,
+            9 => MatrixCoefficients::Bt2020,
+            code => MatrixCoefficients::Other(code),
+        }
+    }
+}
+
+impl ColorDescription {
+    /// SDTV, which decoders also assume for streams without a color description.
+    pub const BT601: ColorDescription = ColorDescription {
+        primaries: ColorPrimaries::Bt601,
+        transfer: TransferCharacteristics::Bt601,
+        matrix: MatrixCoefficients::Bt601,
+    };
+    pub const BT709: ColorDescription = ColorDescription {
+        primaries: ColorPrimaries::Bt709,
+        transfer: TransferCharacteristics::Bt709,
+        matrix: MatrixCoefficients::Bt709,
+    };
+    pub const BT2020: ColorDescription = ColorDescription {
+        primaries: ColorPrimaries::Bt2020,
+        transfer: TransferCharacteristics::Bt2020,
+        matrix: MatrixCoefficients::Bt2020,
+    };
+    /// sRGB content as sYCC, which uses the BT.601 matrix and usually full range.
+    pub const SRGB: ColorDescription = ColorDescription {
+        primaries: ColorPrimaries::Bt709,
+        transfer: TransferCharacteristics::Srgb,
+        matrix: MatrixCoefficients::Bt601,
+    };
+}
+
+impl Default for VideoSignal {
+    fn default() -> Self {
+        VideoSignal {
+            format: VideoFormat::Unspecified,
+            full_range: false,
+            color: None,
+        }
+    }
+}
+
+impl Vui {
+    pub fn new() -> Self {
+        Vui::default()
+    }
+
+    pub fn sample_aspect_ratio(mut self, width: u16, height: u16) -> Self {
+        self.sample_aspect_ratio = Some(SampleAspectRatio { width, height });
+        self
+    }
+
+    pub fn video_format(mut self, format: VideoFormat) -> Self {
+        self.video_signal
+            .get_or_insert_with(VideoSignal::default)
+            .format = format;
+        self
+    }
+
+    pub fn full_range(mut self, full_range: bool) -> Self {
+        self.video_signal
+            .get_or_insert_with(VideoSignal::default)
+            .full_range = full_range;
+        self
+    }
+
+    pub fn color(mut self, color: ColorDescription) -> Self {
+        self.video_signal
+            .get_or_insert_with(VideoSignal::default)
+            .color = Some(color);
+        self
+    }
+
+    /// Validates the VUI and writes it into every spatial layer of `param`, leaving
+    /// unrelated fields alone. A running encoder picks it up through `Encoder::reconfigure`.
+    pub fn apply(&self, param: &mut SEncParamExt) -> Result<()> {
+        let aspect_ratio = match self.sample_aspect_ratio {
+            Some(SampleAspectRatio { width, height }) if width == 0 || height == 0 => {
+                return Err(Error::InvalidArgument(format!(
+                    "Sample aspect ratio {}:{} isn't positive",
+                    width, height
+                )))
+            }
+            Some(SampleAspectRatio { width, height }) => Some(
+                match ASPECT_RATIOS[..ASP_160x99 as usize]
+                    .iter()
+                    .position(|&ratio| ratio == (width, height))
+                {
+                    Some(index) => (index as ESampleAspectRatio + 1, 0, 0),
+                    None => (ASP_EXT_SAR, width, height),
+                },
+            ),
+            None => None,
+        };
+        for layer in param.sSpatialLayers.iter_mut() {
+            layer.bAspectRatioPresent = aspect_ratio.is_some();
+            if let Some((idc, width, height)) = aspect_ratio {
+                layer.eAspectRatio = idc;
+                layer.sAspectRatioExtWidth = width;
+                layer.sAspectRatioExtHeight = height;
+            }
+            layer.bVideoSignalTypePresent = self.video_signal.is_some();
+            if let Some(signal) = self.video_signal {
+                layer.uiVideoFormat = signal.format.code();
+                layer.bFullRange = signal.full_range;
+                layer.bColorDescriptionPresent = signal.color.is_some();
+                if let Some(color) = signal.color {
+                    layer.uiColorPrimaries = color.primaries.code();
+                    layer.uiTransferCharacteristics = color.transfer.code();
+                    layer.uiColorMatrix = color.matrix.code();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the VUI of the first spatial layer back out of `param`, e.g. from
+    /// `Encoder::params()`.
+    pub fn from_params(param: &SEncParamExt) -> Self {
+        Vui::from_layer(&param.sSpatialLayers[0])
+    }
+
+    fn from_layer(layer: &SSpatialLayerConfig) -> Self {
+        let sample_aspect_ratio = match layer.eAspectRatio {
+            _ if !layer.bAspectRatioPresent => None,
+            ASP_EXT_SAR => Some((layer.sAspectRatioExtWidth, layer.sAspectRatioExtHeight)),
+            idc => ASPECT_RATIOS.get((idc as usize).wrapping_sub(1)).cloned(),
+        };
+        let color = if layer.bColorDescriptionPresent {
+            Some(ColorDescription {
+                primaries: ColorPrimaries::from_code(layer.uiColorPrimaries),
+                transfer: TransferCharacteristics::from_code(layer.uiTransferCharacteristics),
+                matrix: MatrixCoefficients::from_code(layer.uiColorMatrix),
+            })
+        } else {
+            None
+        };
+        Vui {
+            sample_aspect_ratio: sample_aspect_ratio
+                .map(|(width, height)| SampleAspectRatio { width, height }),
+            video_signal: if layer.bVideoSignalTypePresent {
+                Some(VideoSignal {
+                    format: VideoFormat::from_code(layer.uiVideoFormat),
+                    full_range: layer.bFullRange,
+                    color,
+                })
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Reads the VUI back out of encoded SPS NAL units, for tests.
+#[cfg(test)]
+pub(crate) mod sps {
+    use super::*;
+    use nal::{self, NAL_SPS, NAL_SUBSET_SPS};
+
+    const EXTENDED_SAR: u32 = 255;
+
+    fn truncated() -> Error {
+        Error::InvalidArgument("SPS ends early".to_string())
+    }
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        position: usize,
+    }
+
+    impl<'a> BitReader<'a> {
+        fn bit(&mut self) -> Result<bool> {
+            let byte = *self.data.get(self.position >> 3).ok_or_else(truncated)?;
+            let bit = byte >> (7 - (self.position & 7)) & 1 != 0;
+            self.position += 1;
+            Ok(bit)
+        }
+
+        fn bits(&mut self, count: u32) -> Result<u32> {
+            let mut value = 0;
+            for _ in 0..count {
+                value = value << 1 | u32::from(self.bit()?);
+            }
+            Ok(value)
+        }
+
+        /// `ue(v)`.
+        fn ue(&mut self) -> Result<u32> {
+            let mut zeros = 0;
+            while !self.bit()? {
+                zeros += 1;
+                if zeros > 31 {
+                    return Err(Error::InvalidArgument(
+                        "Exp-Golomb code in SPS is too long".to_string(),
+                    ));
+                }
+            }
+            Ok(((1u64 << zeros) - 1 + u64::from(self.bits(zeros)?)) as u32)
+        }
+
+        /// `se(v)`, of which only the length matters here.
+        fn skip_se(&mut self) -> Result<()> {
+            self.ue().map(|_| ())
+        }
+    }
+
+    /// Drops the emulation prevention bytes of a NAL unit payload.
+    fn unescape(payload: &[u8]) -> Vec<u8> {
+        let mut rbsp = Vec::with_capacity(payload.len());
+        let mut zeros = 0;
+        for &byte in payload {
+            if zeros >= 2 && byte == 3 {
+                zeros = 0;
+                continue;
+            }
+            zeros = if byte == 0 { zeros + 1 } else { 0 };
+            rbsp.push(byte);
+        }
+        rbsp
+    }
+
+    /// The RBSP of an SPS or subset SPS NAL unit.
+    fn sps_rbsp(nal: &[u8]) -> Result<Vec<u8>> {
+        let unit = nal::nal_units(nal)
+            .next()
+            .ok_or_else(|| Error::InvalidArgument("No NAL unit in SPS buffer".to_string()))?;
+        match unit.nal_type() {
+            NAL_SPS | NAL_SUBSET_SPS => {}
+            other => {
+                return Err(Error::InvalidArgument(format!(
+                    "NAL unit type {} isn't an SPS",
+                    other
+                )))
+            }
+        }
+        Ok(unescape(&unit.payload()[1..]))
+    }
+
+    /// Reads `seq_parameter_set_data()` up to and including `vui_parameters_present_flag`.
+    fn skip_to_vui(reader: &mut BitReader) -> Result<bool> {
+        let profile_idc = reader.bits(8)?;
+        reader.bits(16)?; // Constraint flags and level_idc.
+        reader.ue()?; // seq_parameter_set_id
+        if matches!(
+            profile_idc,
+            100 | 110 | 122 | 244 | 44 | 83 | 86 | 118 | 128 | 138 | 139 | 134 | 135
+        ) {
+            if reader.ue()? == 3 {
+                reader.bit()?; // separate_colour_plane_flag
+            }
+            reader.ue()?; // bit_depth_luma_minus8
+            reader.ue()?; // bit_depth_chroma_minus8
+            reader.bit()?; // qpprime_y_zero_transform_bypass_flag
+            if reader.bit()? {
+                return Err(Error::InvalidArgument(
+                    "SPS scaling matrices aren't supported".to_string(),
+                ));
+            }
+        }
+        reader.ue()?; // log2_max_frame_num_minus4
+        match reader.ue()? {
+            0 => {
+                reader.ue()?; // log2_max_pic_order_cnt_lsb_minus4
+            }
+            1 => {
+                reader.bit()?; // delta_pic_order_always_zero_flag
+                reader.skip_se()?; // offset_for_non_ref_pic
+                reader.skip_se()?; // offset_for_top_to_bottom_field
+                for _ in 0..reader.ue()? {
+                    reader.skip_se()?; // offset_for_ref_frame
+                }
+            }
+            _ => {}
+        }
+        reader.ue()?; // max_num_ref_frames
+        reader.bit()?; // gaps_in_frame_num_value_allowed_flag
+        reader.ue()?; // pic_width_in_mbs_minus1
+        reader.ue()?; // pic_height_in_map_units_minus1
+        if !reader.bit()? {
+            reader.bit()?; // mb_adaptive_frame_field_flag
+        }
+        reader.bit()?; // direct_8x8_inference_flag
+        if reader.bit()? {
+            for _ in 0..4 {
+                reader.ue()?; // frame_crop_*_offset
+            }
+        }
+        reader.bit()
+    }
+
+    /// Reads the VUI fields up to the video signal type.
+    fn read_front(reader: &mut BitReader) -> Result<Vui> {
+        let mut vui = Vui::new();
+        if reader.bit()? {
+            let ratio = match reader.bits(8)? {
+                EXTENDED_SAR => Some((reader.bits(16)? as u16, reader.bits(16)? as u16)),
+                idc => ASPECT_RATIOS.get((idc as usize).wrapping_sub(1)).cloned(),
+            };
+            vui.sample_aspect_ratio =
+                ratio.map(|(width, height)| SampleAspectRatio { width, height });
+        }
+        if reader.bit()? {
+            reader.bit()?; // overscan_appropriate_flag
+        }
+        if reader.bit()? {
+            let format = VideoFormat::from_code(reader.bits(3)? as u8);
+            let full_range = reader.bit()?;
+            let color = if reader.bit()? {
+                Some(ColorDescription {
+                    primaries: ColorPrimaries::from_code(reader.bits(8)? as u8),
+                    transfer: TransferCharacteristics::from_code(reader.bits(8)? as u8),
+                    matrix: MatrixCoefficients::from_code(reader.bits(8)? as u8),
+                })
+            } else {
+                None
+            };
+            vui.video_signal = Some(VideoSignal {
+                format,
+                full_range,
+                color,
+            });
+        }
+        Ok(vui)
+    }
+
+    /// The VUI fields of an SPS or subset SPS NAL unit, `None` without a VUI.
+    pub(crate) fn read_sps_vui(nal: &[u8]) -> Result<Option<Vui>> {
+        let rbsp = sps_rbsp(nal)?;
+        let mut reader = BitReader {
+            data: &rbsp,
+            position: 0,
+        };
+        if !skip_to_vui(&mut reader)? {
+            return Ok(None);
+        }
+        read_front(&mut reader).map(Some)
+    }
+}
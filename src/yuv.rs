@@ -0,0 +1,147 @@
+//! Borrowed I420 pictures that are checked once and then handed to the encoder without a
+//! copy.
+//!
+//! `YuvFrameRef::new` takes the planes and strides straight from a capture buffer and
+//! rejects any layout that would let openh264 read past the end of a plane.
+
+use std::os::raw::c_int;
+use std::ptr::null_mut;
+
+use error::{Error, Result};
+use {videoFormatI420, SSourcePicture};
+
+/// I420 picture borrowed from caller-owned planes. The chroma planes are half the luma
+/// size in both directions, so the width and height must be even.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct YuvFrameRef<'a> {
+    width: usize,
+    height: usize,
+    planes: [&'a [u8]; 3],
+    strides: [usize; 3],
+    /// Milliseconds, passed to openh264 as `uiTimeStamp`.
+    pub timestamp: i64,
+}
+
+fn check_size(width: usize, height: usize) -> Result<()> {
+    if width == 0
+        || height == 0
+        || width & 1 != 0
+        || height & 1 != 0
+        || width > c_int::MAX as usize
+        || height > c_int::MAX as usize
+    {
+        return Err(Error::InvalidArgument(format!(
+            "I420 picture size {}x{} isn't even and non-zero",
+            width, height
+        )));
+    }
+    Ok(())
+}
+
+impl<'a> YuvFrameRef<'a> {
+    /// Y, U and V `planes` of a `width` x `height` picture, each holding at least
+    /// `strides[i]` bytes per row for all of its rows.
+    pub fn new(
+        width: usize,
+        height: usize,
+        planes: [&'a [u8]; 3],
+        strides: [usize; 3],
+    ) -> Result<YuvFrameRef<'a>> {
+        check_size(width, height)?;
+        let rows = [height, height / 2, height / 2];
+        let row_lengths = [width, width / 2, width / 2];
+        for (index, plane) in planes.iter().enumerate() {
+            let stride = strides[index];
+            if stride < row_lengths[index] || stride > c_int::MAX as usize {
+                return Err(Error::InvalidArgument(format!(
+                    "Stride {} of plane {} doesn't fit rows of {} bytes",
+                    stride, index, row_lengths[index]
+                )));
+            }
+            let fits = match stride.checked_mul(rows[index]) {
+                Some(required) => plane.len() >= required,
+                None => false,
+            };
+            if !fits {
+                return Err(Error::InvalidArgument(format!(
+                    "Plane {} holds {} bytes, less than {} rows of stride {}",
+                    index,
+                    plane.len(),
+                    rows[index],
+                    stride
+                )));
+            }
+        }
+        Ok(YuvFrameRef {
+            width,
+            height,
+            planes,
+            strides,
+            timestamp: 0,
+        })
+    }
+
+    /// Splits one contiguous, tightly packed I420 buffer, as many capture APIs deliver it.
+    pub fn packed(width: usize, height: usize, data: &'a [u8]) -> Result<YuvFrameRef<'a>> {
+        check_size(width, height)?;
+        let required = width
+            .checked_mul(height)
+            .and_then(|luma| luma.checked_add(luma / 2));
+        let fits = match required {
+            Some(required) => data.len() >= required,
+            None => false,
+        };
+        if !fits {
+            return Err(Error::InvalidArgument(format!(
+                "Buffer of {} bytes doesn't hold a {}x{} I420 picture",
+                data.len(),
+                width,
+                height
+            )));
+        }
+        let luma = width * height;
+        let chroma = luma / 4;
+        let (y, chroma_planes) = data.split_at(luma);
+        let (u, v) = chroma_planes.split_at(chroma);
+        YuvFrameRef::new(width, height, [y, u, v], [width, width / 2, width / 2])
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn planes(&self) -> [&'a [u8]; 3] {
+        self.planes
+    }
+
+    pub fn strides(&self) -> [usize; 3] {
+        self.strides
+    }
+
+    /// Points `SSourcePicture` at the borrowed planes. openh264 only reads through
+    /// `pData`, so handing it pointers derived from shared slices is sound.
+    pub(crate) fn picture(&self) -> SSourcePicture {
+        SSourcePicture {
+            iColorFormat: videoFormatI420 as c_int,
+            iStride: [
+                self.strides[0] as c_int,
+                self.strides[1] as c_int,
+                self.strides[2] as c_int,
+                0,
+            ],
+            pData: [
+                self.planes[0].as_ptr() as *mut u8,
+                self.planes[1].as_ptr() as *mut u8,
+                self.planes[2].as_ptr() as *mut u8,
+                null_mut(),
+            ],
+            iPicWidth: self.width as c_int,
+            iPicHeight: self.height as c_int,
+            uiTimeStamp: self.timestamp,
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Encoder to decoder round trips across a matrix of profiles, slice modes, spatial and
+//! temporal layers, LTR and frame sizes. Every configuration must come back frame for
+//! frame at the right size and timestamp and close to the input, so a change of the
+//! openh264 version `build.rs` builds shows up here before it reaches users.
+
+extern crate openh264_sys;
+
+use openh264_sys::svc::{SpatialLayer, SvcLayout};
+use openh264_sys::testsrc::{Pattern, TestSource};
+use openh264_sys::*;
+
+const FRAMES: usize = 12;
+const BITRATE: u32 = 2_000_000;
+/// Luma PSNR every decoded frame must reach, in dB.
+const MIN_PSNR: f64 = 30.0;
+
+/// Includes widths and heights that aren't multiples of 16, down to a 50x30 base layer.
+const SIZES: [(usize, usize); 3] = [(100, 60), (176, 144), (212, 116)];
+const PROFILES: [EProfileIdc; 3] = [PRO_BASELINE, PRO_MAIN, PRO_HIGH];
+const SLICE_MODES: [SliceModeEnum; 3] =
+    [SM_SINGLE_SLICE, SM_FIXEDSLCNUM_SLICE, SM_SIZELIMITED_SLICE];
+
+#[derive(Debug, Clone, Copy)]
+struct Config {
+    width: usize,
+    height: usize,
+    profile: EProfileIdc,
+    slice_mode: SliceModeEnum,
+    spatial_layers: usize,
+    temporal_layers: u32,
+    ltr: bool,
+}
+
+impl Config {
+    fn params(&self, encoder: &Encoder) -> SEncParamExt {
+        let (width, height) = (self.width as u32, self.height as u32);
+        let mut layers = vec![SpatialLayer::new(width, height, 30.0, BITRATE)];
+        if self.spatial_layers == 2 {
+            layers[0].bitrate = BITRATE * 3 / 4;
+            layers.insert(
+                0,
+                SpatialLayer::new(width / 2, height / 2, 30.0, BITRATE / 4),
+            );
+        }
+        for layer in &mut layers {
+            layer.profile = self.profile;
+        }
+        let mut layout = SvcLayout::new(layers);
+        layout.temporal_layers = self.temporal_layers;
+
+        let mut param = encoder.default_params().unwrap();
+        param.iUsageType = CAMERA_VIDEO_REAL_TIME;
+        param.bEnableFrameSkip = false;
+        layout.apply(&mut param).unwrap();
+        if self.profile != PRO_BASELINE {
+            param.iEntropyCodingModeFlag = 1;
+        }
+        for layer in &mut param.sSpatialLayers[..self.spatial_layers] {
+            layer.sSliceArgument.uiSliceMode = self.slice_mode;
+            layer.sSliceArgument.uiSliceNum = 4;
+            layer.sSliceArgument.uiSliceSizeConstraint = 600;
+        }
+        param.bEnableLongTermReference = self.ltr;
+        param.iLTRRefNum = if self.ltr { 1 } else { 0 };
+        param
+    }
+}
+
+fn matrix() -> Vec<Config> {
+    let mut configs = Vec::new();
+    for &(width, height) in &SIZES {
+        for &profile in &PROFILES {
+            for &slice_mode in &SLICE_MODES {
+                for spatial_layers in 1..3 {
+                    for &temporal_layers in &[1, 3] {
+                        for &ltr in &[false, true] {
+                            configs.push(Config {
+                                width,
+                                height,
+                                profile,
+                                slice_mode,
+                                spatial_layers,
+                                temporal_layers,
+                                ltr,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+    }
+    configs
+}
+
+/// Encodes `FRAMES` frames with `config` and checks every decoded picture.
+fn round_trip(config: &Config) -> std::result::Result<(), String> {
+    let mut encoder = Encoder::new().map_err(|e| e.to_string())?;
+    let param = config.params(&encoder);
+    encoder
+        .initialize(&param)
+        .map_err(|e| format!("initialize: {}", e))?;
+    let mut decoder = Decoder::new().map_err(|e| e.to_string())?;
+    decoder.initialize(&SDecodingParam::default()).unwrap();
+
+    let source = TestSource::new(Pattern::Gradient, config.width, config.height);
+    for (index, frame) in source.take(FRAMES).enumerate() {
+        let encoded = encoder
+            .encode(&frame.as_yuv().unwrap())
+            .map_err(|e| format!("frame {}: encode: {}", index, e))?;
+        let decoded = decoder
+            .decode_with_timestamp(&encoded.to_bytes(), frame.timestamp as u64)
+            .map_err(|e| format!("frame {}: decode: {}", index, e))?
+            .ok_or_else(|| format!("frame {}: no picture", index))?;
+        if (decoded.width, decoded.height) != (config.width, config.height) {
+            return Err(format!(
+                "frame {}: decoded {}x{}",
+                index, decoded.width, decoded.height
+            ));
+        }
+        if decoded.timestamp != frame.timestamp as u64 {
+            return Err(format!(
+                "frame {}: timestamp {} instead of {}",
+                index, decoded.timestamp, frame.timestamp
+            ));
+        }
+        let quality = metrics::compare(&frame, &decoded).map_err(|e| e.to_string())?;
+        if quality.psnr.y < MIN_PSNR {
+            return Err(format!(
+                "frame {}: luma PSNR {:.2} dB",
+                index, quality.psnr.y
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn encode_decode_matrix() {
+    let failures: Vec<String> = matrix()
+        .iter()
+        .filter_map(|config| {
+            round_trip(config)
+                .err()
+                .map(|error| format!("{:?}: {}", config, error))
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}